@@ -0,0 +1,239 @@
+//! Benchmarks run from the command line, e.g. `arcaders bench collisions`.
+//! Build with `--release` to get meaningful numbers, and with
+//! `--features count-allocations` for `bench pool` to count allocations.
+
+use crate::phi::data::{Rectangle, SpatialHash, Vec2};
+use crate::phi::ecs::{Transform, Velocity, World};
+use crate::phi::pool::Pool;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::time::{Duration, Instant};
+
+/// The number of times every measure is repeated, the fastest run being kept.
+const RUNS: u32 = 10;
+
+#[cfg(feature = "count-allocations")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the allocations made by the program, so that benchmarks can
+    /// tell how many some code makes.
+    struct CountingAllocator;
+
+    pub static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+}
+
+/// The number of allocations made so far, if they are counted.
+#[cfg(feature = "count-allocations")]
+fn allocations() -> Option<usize> {
+    Some(counting::ALLOCATIONS.load(::std::sync::atomic::Ordering::Relaxed))
+}
+
+#[cfg(not(feature = "count-allocations"))]
+fn allocations() -> Option<usize> {
+    None
+}
+
+/// Runs the benchmark called `name`. Returns whether it exists.
+pub fn run(name: &str) -> bool {
+    match name {
+        "collisions" => collisions(),
+        "pool" => pool(),
+        _ => return false,
+    }
+
+    true
+}
+
+/// The fastest of `RUNS` calls to `f`, along with its last result.
+fn measure<T, F: FnMut() -> T>(mut f: F) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = Some(f());
+        best = best.min(start.elapsed());
+    }
+
+    (best, result.unwrap())
+}
+
+/// Compares the nested loop which `GameView` used to find which asteroids are
+/// hit by bullets to a `SpatialHash` of the bullets, rebuilt on every run as it
+/// is every frame.
+fn collisions() {
+    const WORLD_W: f64 = 800.0;
+    const WORLD_H: f64 = 600.0;
+    const ASTEROID_SIZE: f64 = 96.0;
+    const BULLET_W: f64 = 8.0;
+    const BULLET_H: f64 = 4.0;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut random_rects = |count: usize, w: f64, h: f64| -> Vec<Rectangle> {
+        (0..count)
+            .map(|_| Rectangle {
+                x: rng.gen::<f64>() * (WORLD_W - w),
+                y: rng.gen::<f64>() * (WORLD_H - h),
+                w,
+                h,
+            })
+            .collect()
+    };
+
+    println!("{:>10} {:>10} {:>14} {:>14} {:>10}", "asteroids", "bullets", "nested loop", "spatial hash", "hits");
+
+    for &(asteroid_count, bullet_count) in &[(10, 100), (100, 1_000), (500, 5_000), (1_000, 10_000)] {
+        let asteroids = random_rects(asteroid_count, ASTEROID_SIZE, ASTEROID_SIZE);
+        let bullets = random_rects(bullet_count, BULLET_W, BULLET_H);
+
+        let (nested, nested_hits) = measure(|| {
+            let mut hits = 0;
+            for asteroid in &asteroids {
+                for bullet in &bullets {
+                    if asteroid.overlaps(*bullet) {
+                        hits += 1;
+                    }
+                }
+            }
+            hits
+        });
+
+        let (hashed, hashed_hits) = measure(|| {
+            let mut grid = SpatialHash::new(ASTEROID_SIZE);
+            for (i, bullet) in bullets.iter().enumerate() {
+                grid.insert(*bullet, i);
+            }
+
+            asteroids.iter()
+                .map(|asteroid| grid.query(*asteroid).len())
+                .sum::<usize>()
+        });
+
+        // Both methods must find the same collisions to be comparable.
+        assert_eq!(nested_hits, hashed_hits);
+
+        println!("{:>10} {:>10} {:>12.3}ms {:>12.3}ms {:>10}",
+            asteroid_count, bullet_count,
+            nested.as_secs_f64() * 1000.0, hashed.as_secs_f64() * 1000.0,
+            nested_hits);
+    }
+}
+
+/// A bullet of the `pool` benchmark, flying to the right.
+struct Shot {
+    x: f64,
+}
+
+/// The way `GameView` used to update its bullets.
+trait Moving {
+    fn update(self: Box<Self>, dt: f64) -> Option<Box<dyn Moving>>;
+}
+
+impl Moving for Shot {
+    fn update(mut self: Box<Self>, dt: f64) -> Option<Box<dyn Moving>> {
+        self.x += SHOT_SPEED * dt;
+        if self.x > SHOT_RANGE { None } else { Some(self) }
+    }
+}
+
+const SHOT_SPEED: f64 = 240.0;
+const SHOT_RANGE: f64 = 800.0;
+
+/// Counts the allocations made per frame by the bullets of a game, depending on
+/// where they are stored: in a vector of boxes rebuilt every frame, as
+/// `GameView` used to, in a `Pool` updated in place, and in a `World`.
+fn pool() {
+    const DT: f64 = 1.0 / 60.0;
+
+    // Bullets live for 200 frames, so that the containers are full and as
+    // many bullets are destroyed as shot by the end of the warm-up.
+    const WARMUP_FRAMES: usize = 300;
+    const FRAMES: usize = 300;
+
+    /// Runs `frame` for the warm-up, then for the measured frames. Returns the
+    /// allocations, if they are counted, and the time per measured frame, in
+    /// milliseconds.
+    fn per_frame<F: FnMut()>(mut frame: F) -> (Option<f64>, f64) {
+        for _ in 0..WARMUP_FRAMES {
+            frame();
+        }
+
+        let before = allocations();
+        let (time, _) = measure(|| {
+            for _ in 0..FRAMES / RUNS as usize {
+                frame();
+            }
+        });
+        let allocations = before.zip(allocations()).map(|(before, after)| after - before);
+
+        (allocations.map(|allocations| allocations as f64 / FRAMES as f64),
+         time.as_secs_f64() * 1000.0 / (FRAMES / RUNS as usize) as f64)
+    }
+
+    println!("{:>16} {:>12} {:>14} {:>10}", "container", "shots/frame", "allocs/frame", "ms/frame");
+
+    for &shots in &[2, 20, 200] {
+        let mut boxes: Vec<Box<dyn Moving>> = vec![];
+        let boxed = per_frame(|| {
+            boxes = ::std::mem::take(&mut boxes)
+                .into_iter()
+                .filter_map(|shot| shot.update(DT))
+                .collect();
+            for _ in 0..shots {
+                boxes.push(Box::new(Shot { x: 0.0 }));
+            }
+        });
+
+        let mut pool = Pool::new();
+        let pooled = per_frame(|| {
+            pool.retain(|_, shot: &mut Shot| {
+                shot.x += SHOT_SPEED * DT;
+                shot.x <= SHOT_RANGE
+            });
+            for _ in 0..shots {
+                pool.insert(Shot { x: 0.0 });
+            }
+        });
+
+        let mut world = World::new();
+        let bounds = Rectangle::with_size(SHOT_RANGE, SHOT_RANGE);
+        let in_world = per_frame(|| {
+            world.movement(DT);
+            world.despawn_dead(DT, bounds);
+            for _ in 0..shots {
+                let shot = world.spawn(());
+                world.transforms.insert(shot, Transform { rect: Rectangle::with_size(8.0, 4.0), angle: 0.0 });
+                world.velocities.insert(shot, Velocity { vel: Vec2::new(SHOT_SPEED, 0.0), spin: 0.0 });
+            }
+        });
+
+        // The bullets must still be there for the numbers to mean anything.
+        assert!(!boxes.is_empty() && !pool.is_empty() && !world.is_empty());
+
+        for (name, (allocations, ms)) in [("boxed vectors", boxed), ("pool", pooled), ("world", in_world)] {
+            let allocations = allocations.map_or("-".to_string(), |allocations| format!("{:.2}", allocations));
+            println!("{:>16} {:>12} {:>14} {:>10.4}", name, shots, allocations, ms);
+        }
+    }
+}
@@ -1,11 +1,64 @@
 extern crate sdl2;
 extern crate rand;
+extern crate serde;
+extern crate ron;
 
+mod bench;
 mod phi;
 mod views;
 
 fn main() {
+    let args: Vec<String> = ::std::env::args().collect();
+
+    // `pack-atlas <image.png> <atlas.ron> <inputs.png...>` combines the given
+    // images into a single atlas instead of starting the game.
+    if args.len() > 1 && args[1] == "pack-atlas" {
+        if args.len() < 5 {
+            eprintln!("Usage: {} pack-atlas <image.png> <atlas.ron> <inputs.png...>", args[0]);
+            ::std::process::exit(1);
+        }
+
+        if let Err(e) = crate::phi::atlas::pack_files(&args[4..], &args[2], &args[3]) {
+            eprintln!("Could not pack the atlas: {}", e);
+            ::std::process::exit(1);
+        }
+
+        return;
+    }
+
+    // `bench <name>` runs one of the benchmarks of `bench.rs`.
+    if args.len() > 1 && args[1] == "bench" {
+        if args.len() < 3 || !crate::bench::run(&args[2]) {
+            eprintln!("Usage: {} bench <collisions|pool>", args[0]);
+            ::std::process::exit(1);
+        }
+
+        return;
+    }
+
+    // `capture <dir> <every>` plays the game normally, but saves every
+    // `every`-th frame in `dir`, e.g. to make a trailer.
+    let capture =
+        if args.len() > 1 && args[1] == "capture" {
+            match args.get(2).zip(args.get(3).and_then(|every| every.parse::<u64>().ok())) {
+                Some((dir, every)) => Some((dir.clone(), every)),
+                None => {
+                    eprintln!("Usage: {} capture <dir> <every>", args[0]);
+                    ::std::process::exit(1);
+                },
+            }
+        } else {
+            None
+        };
+
     crate::phi::spawn("ArcadeRS Shooter", |phi| {
+        if let Some((ref dir, every)) = capture {
+            if let Err(e) = phi.start_capture(dir, every) {
+                eprintln!("Could not capture frames: {}", e);
+                ::std::process::exit(1);
+            }
+        }
+
         Box::new(crate::views::main_menu::MainMenuView::new(phi))
     });
-}
\ No newline at end of file
+}
@@ -0,0 +1,387 @@
+use crate::phi::data::Rectangle;
+use crate::phi::gfx::{Sprite, AnimatedSprite, Animator, PlaybackMode};
+use serde::{Serialize, Deserialize};
+use sdl2::image::{LoadSurface, SaveSurface};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{BlendMode, WindowCanvas};
+use sdl2::surface::Surface;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The widest and tallest an atlas produced by the packer may be, in pixels,
+/// which any renderer supports as a texture.
+const PACKED_MAX_WIDTH: u32 = 2048;
+const PACKED_MAX_HEIGHT: u32 = 2048;
+
+/// Pixels left empty between packed images, so that scaled sprites do not
+/// bleed into their neighbours.
+const PACKED_PADDING: u32 = 1;
+
+/// The speed given to the animations detected by the packer. It can then be
+/// tweaked in the generated description.
+const PACKED_FPS: f64 = 10.0;
+
+/// A named region of the atlas' image, in pixels.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct FrameDescr {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+/// The frames composing an animation, either listed by name or cut out of a
+/// regular grid in the image.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AnimationDescr {
+    /// A sequence of frames defined in the atlas' `frames`.
+    Frames {
+        frames: Vec<String>,
+        fps: f64,
+        #[serde(default)]
+        mode: PlaybackMode,
+        /// The animation played once this one is finished, if any.
+        #[serde(default)]
+        next: Option<String>,
+    },
+
+    /// A sprite sheet whose frames are ordered from left to right, then top
+    /// to bottom, starting at (`x`, `y`).
+    Grid {
+        #[serde(default)]
+        x: f64,
+        #[serde(default)]
+        y: f64,
+        frame_w: f64,
+        frame_h: f64,
+        frames_wide: usize,
+        frames_high: usize,
+        total_frames: usize,
+        fps: f64,
+        #[serde(default)]
+        mode: PlaybackMode,
+        /// The animation played once this one is finished, if any.
+        #[serde(default)]
+        next: Option<String>,
+    },
+}
+
+/// The content of an atlas description file, stored in RON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AtlasDescr {
+    /// The path to the image, relative to the description file.
+    pub image: String,
+    #[serde(default)]
+    pub frames: BTreeMap<String, FrameDescr>,
+    #[serde(default)]
+    pub animations: BTreeMap<String, AnimationDescr>,
+}
+
+/// A single texture holding many named sprites and animations, as described by
+/// an atlas file.
+pub struct Atlas {
+    animations: BTreeMap<String, AtlasAnimation>,
+}
+
+/// An animation of the atlas, ready to be played.
+struct AtlasAnimation {
+    sprites: Vec<Sprite>,
+    fps: f64,
+    mode: PlaybackMode,
+    next: Option<String>,
+}
+
+impl Atlas {
+    /// Loads the atlas described by the RON file at `path`, along with its
+    /// image. Returns an error message if either could not be read, or if the
+    /// description refers to regions outside of the image.
+    pub fn load(renderer: &WindowCanvas, path: &str) -> Result<Atlas, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let descr: AtlasDescr = ron::from_str(&source)
+            .map_err(|e| format!("{}: {}", path, e))?;
+
+        let image_path = Path::new(path).parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&descr.image);
+        let image = Sprite::load(renderer, image_path.to_str().unwrap())
+            .ok_or_else(|| format!("{}: could not load {}", path, descr.image))?;
+
+        let mut frames = BTreeMap::new();
+        for (name, frame) in &descr.frames {
+            let sprite = image.region(Rectangle { x: frame.x, y: frame.y, w: frame.w, h: frame.h })
+                .ok_or_else(|| format!("{}: frame `{}` is outside of the image", path, name))?;
+            frames.insert(name.clone(), sprite);
+        }
+
+        let mut animations = BTreeMap::new();
+        for (name, animation) in &descr.animations {
+            let (sprites, fps, mode, next) = match *animation {
+                AnimationDescr::Frames { frames: ref names, fps, mode, ref next } => {
+                    let sprites = names.iter()
+                        .map(|frame| frames.get(frame).cloned()
+                            .ok_or_else(|| format!("{}: animation `{}` uses unknown frame `{}`", path, name, frame)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    (sprites, fps, mode, next)
+                },
+
+                AnimationDescr::Grid { x, y, frame_w, frame_h, frames_wide, frames_high, total_frames, fps, mode, ref next } => {
+                    let mut sprites = Vec::with_capacity(total_frames);
+
+                    for yth in 0..frames_high {
+                        for xth in 0..frames_wide {
+                            if frames_wide * yth + xth >= total_frames {
+                                break;
+                            }
+
+                            sprites.push(image.region(Rectangle {
+                                w: frame_w,
+                                h: frame_h,
+                                x: x + frame_w * xth as f64,
+                                y: y + frame_h * yth as f64,
+                            }).ok_or_else(|| format!("{}: animation `{}` is outside of the image", path, name))?);
+                        }
+                    }
+
+                    (sprites, fps, mode, next)
+                },
+            };
+
+            if sprites.is_empty() || fps == 0.0 {
+                return Err(format!("{}: animation `{}` has no frames or a null fps", path, name));
+            }
+
+            animations.insert(name.clone(), AtlasAnimation {
+                sprites,
+                fps,
+                mode,
+                next: next.clone(),
+            });
+        }
+
+        for (name, animation) in &animations {
+            if let Some(ref next) = animation.next {
+                if !animations.contains_key(next) {
+                    return Err(format!("{}: animation `{}` is followed by unknown animation `{}`", path, name, next));
+                }
+            }
+        }
+
+        Ok(Atlas {
+            animations,
+        })
+    }
+
+    /// Returns the animation called `name`, playing at the speed defined in
+    /// the atlas file.
+    pub fn animated(&self, name: &str) -> Option<AnimatedSprite> {
+        self.animations.get(name).map(|animation| {
+            let mut sprite = AnimatedSprite::with_fps(animation.sprites.clone(), animation.fps);
+            sprite.set_mode(animation.mode);
+            sprite
+        })
+    }
+
+    /// Returns an animator holding every animation of the atlas as a clip,
+    /// along with the transitions between them, which starts by playing the
+    /// animation called `initial`.
+    pub fn animator(&self, initial: &str) -> Option<Animator> {
+        let mut animator = Animator::new(initial, self.animated(initial)?);
+
+        for (name, animation) in &self.animations {
+            animator.add_clip(name, self.animated(name).unwrap());
+
+            if let Some(ref next) = animation.next {
+                animator.add_transition(name, next);
+            }
+        }
+
+        Some(animator)
+    }
+}
+
+/// The positions of images packed in an atlas, and the size of the atlas, in
+/// pixels.
+type Packing = (Vec<(u32, u32)>, (u32, u32));
+
+/// Computes where images of the given sizes should be placed in an atlas, by
+/// stacking them in horizontal shelves, tallest first. Returns the position of
+/// every image, in the same order as `sizes`, and the size of the atlas, or
+/// the index of an image which does not fit in it.
+pub fn pack(sizes: &[(u32, u32)]) -> Result<Packing, usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].1.cmp(&sizes[a].1));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut shelf_x, mut shelf_y, mut shelf_h) = (0, 0, 0);
+    let mut atlas_w = 0;
+
+    for i in order {
+        let (w, h) = sizes[i];
+
+        // Start a new shelf below the current one once it is full.
+        if shelf_x > 0 && shelf_x + w > PACKED_MAX_WIDTH {
+            shelf_y += shelf_h + PACKED_PADDING;
+            shelf_x = 0;
+            shelf_h = 0;
+        }
+
+        if w > PACKED_MAX_WIDTH || shelf_y + h > PACKED_MAX_HEIGHT {
+            return Err(i);
+        }
+
+        positions[i] = (shelf_x, shelf_y);
+        shelf_x += w + PACKED_PADDING;
+        shelf_h = shelf_h.max(h);
+        atlas_w = atlas_w.max(shelf_x - PACKED_PADDING);
+    }
+
+    Ok((positions, (atlas_w, shelf_y + shelf_h)))
+}
+
+/// Splits a frame name such as `explode_12` into the name of the animation it
+/// belongs to and its index in that animation.
+fn animation_frame(name: &str) -> Option<(&str, usize)> {
+    let (prefix, index) = name.rsplit_once('_')?;
+    index.parse().ok().map(|index| (prefix, index))
+}
+
+/// Combines the PNG images at `inputs` into a single image saved at
+/// `image_out`, and writes the matching description to `descr_out`.
+///
+/// Every image becomes a frame named after its file. Images named like
+/// `explode_0.png`, `explode_1.png`, ... also form an animation, here called
+/// `explode`.
+pub fn pack_files(inputs: &[String], image_out: &str, descr_out: &str) -> Result<(), String> {
+    let _image_context = sdl2::image::init(sdl2::image::InitFlag::PNG)?;
+
+    let mut images = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut surface = Surface::from_file(input)?;
+        // Copy the pixels as-is, including their transparency.
+        surface.set_blend_mode(BlendMode::None)?;
+
+        let name = Path::new(input).file_stem().and_then(|stem| stem.to_str())
+            .ok_or_else(|| format!("{}: invalid file name", input))?
+            .to_string();
+        images.push((name, surface));
+    }
+
+    let sizes: Vec<_> = images.iter().map(|(_, surface)| surface.size()).collect();
+    let (positions, (atlas_w, atlas_h)) = pack(&sizes)
+        .map_err(|i| format!("{}: does not fit in a {}x{} atlas", inputs[i], PACKED_MAX_WIDTH, PACKED_MAX_HEIGHT))?;
+
+    let mut atlas = Surface::new(atlas_w.max(1), atlas_h.max(1), PixelFormatEnum::RGBA32)?;
+    let mut descr = AtlasDescr {
+        image: Path::new(image_out).file_name().and_then(|name| name.to_str())
+            .ok_or_else(|| format!("{}: invalid file name", image_out))?
+            .to_string(),
+        frames: BTreeMap::new(),
+        animations: BTreeMap::new(),
+    };
+    let mut sequences: BTreeMap<String, Vec<(usize, String)>> = BTreeMap::new();
+
+    for ((name, surface), &(x, y)) in images.iter().zip(positions.iter()) {
+        let (w, h) = surface.size();
+        surface.blit(None, &mut atlas, sdl2::rect::Rect::new(x as i32, y as i32, w, h))?;
+
+        descr.frames.insert(name.clone(), FrameDescr {
+            x: x as f64,
+            y: y as f64,
+            w: w as f64,
+            h: h as f64,
+        });
+
+        if let Some((animation, index)) = animation_frame(name) {
+            sequences.entry(animation.to_string()).or_default()
+                .push((index, name.clone()));
+        }
+    }
+
+    for (animation, mut frames) in sequences {
+        frames.sort();
+        descr.animations.insert(animation, AnimationDescr::Frames {
+            frames: frames.into_iter().map(|(_, name)| name).collect(),
+            fps: PACKED_FPS,
+            mode: PlaybackMode::Loop,
+            next: None,
+        });
+    }
+
+    atlas.save(image_out)?;
+
+    let source = ron::ser::to_string_pretty(&descr, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())?;
+    fs::write(descr_out, source).map_err(|e| format!("{}: {}", descr_out, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The position and size of a packed image.
+    type Placed = (u32, u32, u32, u32);
+
+    /// The rectangles covered by the images of the given `sizes` once packed.
+    fn packed(sizes: &[(u32, u32)]) -> (Vec<Placed>, (u32, u32)) {
+        let (positions, size) = pack(sizes).unwrap();
+        let rects = positions.iter().zip(sizes)
+            .map(|(&(x, y), &(w, h))| (x, y, w, h))
+            .collect();
+        (rects, size)
+    }
+
+    #[test]
+    fn packed_images_do_not_overlap() {
+        let sizes = [(30, 20), (64, 64), (10, 50), (64, 64), (1, 1), (100, 5)];
+        let (rects, (atlas_w, atlas_h)) = packed(&sizes);
+
+        for (i, &(x, y, w, h)) in rects.iter().enumerate() {
+            assert!(x + w <= atlas_w && y + h <= atlas_h, "image {} is outside of the atlas", i);
+
+            // Padding included, no two images touch.
+            for &(other_x, other_y, other_w, other_h) in &rects[i + 1..] {
+                assert!(x + w + PACKED_PADDING <= other_x || other_x + other_w + PACKED_PADDING <= x ||
+                        y + h + PACKED_PADDING <= other_y || other_y + other_h + PACKED_PADDING <= y,
+                        "image {} overlaps another", i);
+            }
+        }
+
+        // The tallest come first, on a single shelf.
+        assert_eq!(rects[1], (0, 0, 64, 64));
+        assert_eq!(rects[3], (65, 0, 64, 64));
+        assert_eq!((atlas_w, atlas_h), (64 + 64 + 10 + 30 + 100 + 1 + 5 * PACKED_PADDING, 64));
+    }
+
+    #[test]
+    fn full_shelves_wrap_to_a_new_one() {
+        // Two of them fit side by side, padding included.
+        let half = (PACKED_MAX_WIDTH - PACKED_PADDING) / 2;
+        let (rects, (atlas_w, atlas_h)) = packed(&[(half, 40), (half, 30), (half, 20)]);
+
+        assert_eq!(rects[0], (0, 0, half, 40));
+        assert_eq!(rects[1], (half + PACKED_PADDING, 0, half, 30));
+        assert_eq!(rects[2], (0, 40 + PACKED_PADDING, half, 20));
+        assert_eq!((atlas_w, atlas_h), (2 * half + PACKED_PADDING, 40 + PACKED_PADDING + 20));
+    }
+
+    #[test]
+    fn images_too_big_for_the_atlas_are_reported() {
+        assert_eq!(pack(&[(10, 10), (PACKED_MAX_WIDTH + 1, 10)]), Err(1));
+        assert_eq!(pack(&[(10, PACKED_MAX_HEIGHT + 1)]), Err(0));
+        assert!(pack(&[(PACKED_MAX_WIDTH, PACKED_MAX_HEIGHT)]).is_ok());
+
+        // Or too many, once the shelves reach the bottom.
+        let row = (PACKED_MAX_WIDTH, (PACKED_MAX_HEIGHT - PACKED_PADDING) / 2);
+        assert_eq!(pack(&[row, row, row]), Err(2));
+    }
+
+    #[test]
+    fn numbered_frames_form_animations() {
+        assert_eq!(animation_frame("explode_12"), Some(("explode", 12)));
+        assert_eq!(animation_frame("big_rock_0"), Some(("big_rock", 0)));
+        assert_eq!(animation_frame("ship"), None);
+        assert_eq!(animation_frame("ship_left"), None);
+    }
+}
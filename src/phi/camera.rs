@@ -0,0 +1,86 @@
+use rand::Rng;
+use sdl2::rect::Rect as SdlRect;
+use sdl2::render::WindowCanvas;
+
+/// Pixels per second by which the intensity of a screen shake decreases.
+const DEFAULT_SHAKE_DECAY: f64 = 30.0;
+
+/// The largest distance, in pixels, by which the view may shake.
+const MAX_SHAKE: f64 = 20.0;
+
+/// Transforms everything which is rendered, so that the view can be moved,
+/// zoomed and shaken without the entities knowing about it.
+///
+/// The transform is applied by the renderer itself, through its viewport and
+/// scale, so that rendering code keeps using window coordinates.
+pub struct Camera {
+    /// The position, in window coordinates, which is shown in the top-left
+    /// corner of the window.
+    pub offset: (f64, f64),
+
+    /// How much everything is magnified, around the center of the window.
+    pub zoom: f64,
+
+    /// Pixels per second by which the intensity of a shake decreases.
+    pub shake_decay: f64,
+
+    /// The largest distance, in pixels, by which the view currently shakes.
+    shake: f64,
+
+    /// The displacement caused by the shake during the current frame.
+    shake_offset: (f64, f64),
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            offset: (0.0, 0.0),
+            zoom: 1.0,
+            shake_decay: DEFAULT_SHAKE_DECAY,
+            shake: 0.0,
+            shake_offset: (0.0, 0.0),
+        }
+    }
+
+    /// Shake the view by up to `intensity` pixels. Shakes add up to a limit,
+    /// and fade out at `shake_decay` pixels per second.
+    pub fn shake(&mut self, intensity: f64) {
+        self.shake = (self.shake + intensity).min(MAX_SHAKE);
+    }
+
+    /// Make the shake fade out and pick its displacement for the next frame.
+    pub fn update<R: Rng>(&mut self, elapsed: f64, rng: &mut R) {
+        self.shake = (self.shake - self.shake_decay * elapsed).max(0.0);
+
+        let shake = self.shake;
+        let mut random = || (rng.gen::<f64>() * 2.0 - 1.0) * shake;
+        self.shake_offset = (random(), random());
+    }
+
+    /// Configure the renderer so that subsequent rendering goes through the
+    /// camera.
+    pub fn apply(&self, renderer: &mut WindowCanvas) {
+        let (win_w, win_h) = renderer.output_size().unwrap();
+        let (win_w, win_h) = (win_w as f64, win_h as f64);
+        let zoom = self.zoom;
+
+        // A point `p` of the window ends up at `(p - offset - center) * zoom +
+        // center + shake`. SDL multiplies the viewport's origin by the scale,
+        // hence the divisions.
+        let x = (win_w / 2.0 + self.shake_offset.0) / zoom - self.offset.0 - win_w / 2.0;
+        let y = (win_h / 2.0 + self.shake_offset.1) / zoom - self.offset.1 - win_h / 2.0;
+
+        // The viewport must extend to the bottom-right corner of the window.
+        let w = (win_w / zoom - x).ceil().max(1.0);
+        let h = (win_h / zoom - y).ceil().max(1.0);
+
+        renderer.set_scale(zoom as f32, zoom as f32).unwrap();
+        renderer.set_viewport(SdlRect::new(x.round() as i32, y.round() as i32, w as u32, h as u32));
+    }
+
+    /// Render in window coordinates again, e.g. for the HUD.
+    pub fn reset(renderer: &mut WindowCanvas) {
+        renderer.set_scale(1.0, 1.0).unwrap();
+        renderer.set_viewport(None);
+    }
+}
@@ -0,0 +1,79 @@
+use sdl2::image::SaveSurface;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::WindowCanvas;
+use sdl2::surface::Surface;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The directory in which screenshots are saved, relative to the working
+/// directory.
+const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Saves what was rendered to the window during the current frame as a PNG
+/// image at `path`. It must be called before the frame is presented.
+pub fn save_png(renderer: &WindowCanvas, path: &Path) -> Result<(), String> {
+    let size = renderer.output_size()?;
+    let mut pixels = renderer.read_pixels(None, PixelFormatEnum::RGBA32)?;
+    save_pixels(size, &mut pixels, path)
+}
+
+/// Saves an image of the given size, made of tightly packed RGBA `pixels`, as
+/// a PNG image at `path`.
+pub fn save_pixels((w, h): (u32, u32), pixels: &mut [u8], path: &Path) -> Result<(), String> {
+    let format = PixelFormatEnum::RGBA32;
+    let surface = Surface::from_data(pixels, w, h, format.byte_size_of_pixels(w as usize) as u32, format)?;
+    surface.save(path)
+}
+
+/// The path of a new screenshot, named after the current time so that former
+/// ones are not overwritten. The directory is created if necessary.
+pub fn screenshot_path() -> Result<PathBuf, String> {
+    fs::create_dir_all(SCREENSHOT_DIR).map_err(|e| format!("{}: {}", SCREENSHOT_DIR, e))?;
+
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+    Ok(Path::new(SCREENSHOT_DIR).join(format!("screenshot_{}.png", millis)))
+}
+
+/// Saves every `every`-th frame to a directory, e.g. to make trailers.
+pub struct FrameCapture {
+    dir: PathBuf,
+    every: u64,
+
+    /// The number of frames rendered, and of frames saved, since the capture
+    /// started.
+    frame: u64,
+    saved: u64,
+}
+
+impl FrameCapture {
+    /// Starts saving frames to `dir`, which is created if necessary.
+    pub fn new(dir: &str, every: u64) -> Result<FrameCapture, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+
+        Ok(FrameCapture {
+            dir: PathBuf::from(dir),
+            every: every.max(1),
+            frame: 0,
+            saved: 0,
+        })
+    }
+
+    /// Called once per rendered frame, before it is presented. The frames are
+    /// numbered from 0, so that they can be fed to a video encoder.
+    pub fn frame(&mut self, renderer: &WindowCanvas) -> Result<(), String> {
+        let frame = self.frame;
+        self.frame += 1;
+
+        // Frames 0, `every`, twice `every`, and so on, are saved.
+        if frame != self.saved * self.every {
+            return Ok(());
+        }
+
+        let path = self.dir.join(format!("frame_{:06}.png", self.saved));
+        self.saved += 1;
+        save_png(renderer, &path)
+    }
+}
@@ -0,0 +1,93 @@
+use crate::phi::Events;
+use crate::phi::gfx::BitmapFont;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect as SdlRect;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+/// The font used by the console. Its glyphs are upper case only.
+const CONSOLE_FONT_PATH: &str = "assets/pixel_font.fnt";
+const CONSOLE_FONT_SCALE: u32 = 2;
+
+/// The number of lines of output shown above the prompt.
+const CONSOLE_LINES: usize = 12;
+
+/// The character which opens and closes the console. It is never typed into
+/// the console itself.
+const CONSOLE_KEY: char = '`';
+
+const MARGIN: f64 = 8.0;
+
+/// A drop-down console, toggled with the backtick key, through which commands
+/// are sent to Phi and to the current view.
+pub struct Console {
+    pub open: bool,
+    font: Option<BitmapFont>,
+
+    /// What is being typed.
+    input: String,
+
+    /// The commands entered so far, and their output, the most recent last.
+    log: Vec<String>,
+}
+
+impl Console {
+    pub fn new(renderer: &WindowCanvas) -> Console {
+        Console {
+            open: false,
+            font: BitmapFont::load(renderer, CONSOLE_FONT_PATH).ok(),
+            input: String::new(),
+            log: vec![],
+        }
+    }
+
+    /// Edit the current line with what was typed during this frame. Returns
+    /// the line once it is submitted with the return key.
+    pub fn input(&mut self, events: &Events) -> Option<String> {
+        self.input.extend(events.now.text.chars().filter(|&c| c != CONSOLE_KEY));
+
+        if events.now.key_backspace == Some(true) {
+            self.input.pop();
+        }
+
+        if events.now.key_enter == Some(true) && !self.input.trim().is_empty() {
+            let line = ::std::mem::take(&mut self.input);
+            self.print(format!("> {}", line));
+            return Some(line);
+        }
+
+        None
+    }
+
+    /// Add `text` to the output of the console.
+    pub fn print(&mut self, text: String) {
+        self.log.push(text);
+    }
+
+    /// Render the console over the top of the window.
+    pub fn render(&self, renderer: &mut WindowCanvas) {
+        let font = match self.font {
+            Some(ref font) => font,
+            None => return,
+        };
+
+        let (win_w, _) = renderer.output_size().unwrap();
+        let (_, line_h) = font.measure(" ", CONSOLE_FONT_SCALE);
+        let h = line_h * (CONSOLE_LINES + 1) as f64 + MARGIN * 2.0;
+
+        let old_blend = renderer.blend_mode();
+        renderer.set_blend_mode(BlendMode::Blend);
+        renderer.set_draw_color(Color::RGBA(20, 5, 20, 210));
+        renderer.fill_rect(SdlRect::new(0, 0, win_w, h as u32)).unwrap();
+        renderer.set_draw_color(Color::RGB(140, 30, 140));
+        renderer.fill_rect(SdlRect::new(0, h as i32, win_w, 2)).unwrap();
+        renderer.set_blend_mode(old_blend);
+
+        // The latest output sits right above the prompt.
+        let shown = &self.log[self.log.len().saturating_sub(CONSOLE_LINES)..];
+        let mut lines = vec![String::new(); CONSOLE_LINES - shown.len()];
+        lines.extend(shown.iter().cloned());
+        lines.push(format!("> {}_", self.input));
+
+        font.render(renderer, &lines.join("\n"), MARGIN, MARGIN, CONSOLE_FONT_SCALE);
+    }
+}
@@ -1,4 +1,105 @@
 use sdl2::rect::Rect as SdlRect;
+use std::collections::HashMap;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// The number of points approximating a circle in `Shape::outline`.
+const CIRCLE_OUTLINE_POINTS: usize = 24;
+
+/// A position, a displacement or a velocity, in window coordinates, where `y`
+/// increases downwards.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub fn new(x: f64, y: f64) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    pub fn dot(self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn distance(self, other: Vec2) -> f64 {
+        (other - self).length()
+    }
+
+    /// The vector of length 1 pointing in the same direction, or the zero
+    /// vector itself, which has no direction.
+    pub fn normalize(self) -> Vec2 {
+        let length = self.length();
+        if length == 0.0 { self } else { self / length }
+    }
+
+    /// The point which is a fraction `t` of the way from `self` to `to`.
+    pub fn lerp(self, to: Vec2, t: f64) -> Vec2 {
+        self + (to - self) * t
+    }
+
+    /// Rotate the vector by `angle` degrees, clockwise on the screen.
+    pub fn rotate(self, angle: f64) -> Vec2 {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Vec2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, factor: f64) -> Vec2 {
+        Vec2::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl Div<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, divisor: f64) -> Vec2 {
+        Vec2::new(self.x / divisor, self.y / divisor)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        *self = *self - other;
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Rectangle {
@@ -60,6 +161,54 @@ impl Rectangle {
         self.y + self.h > other.y
     }
 
+    /// Whether `point` is inside of the rectangle. Points on its right and
+    /// bottom edges are not, so that a point is inside of only one of the
+    /// rectangles tiling a region.
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        point.x >= self.x && point.x < self.x + self.w &&
+        point.y >= self.y && point.y < self.y + self.h
+    }
+
+    /// The region covered by both rectangles, if they overlap.
+    pub fn intersection(self, other: Rectangle) -> Option<Rectangle> {
+        if !self.overlaps(other) {
+            return None;
+        }
+
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        Some(Rectangle {
+            x,
+            y,
+            w: (self.x + self.w).min(other.x + other.w) - x,
+            h: (self.y + self.h).min(other.y + other.h) - y,
+        })
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    pub fn union(self, other: Rectangle) -> Rectangle {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Rectangle {
+            x,
+            y,
+            w: (self.x + self.w).max(other.x + other.w) - x,
+            h: (self.y + self.h).max(other.y + other.h) - y,
+        }
+    }
+
+    /// Move the rectangle by `offset`.
+    pub fn translate(self, offset: Vec2) -> Rectangle {
+        Rectangle {
+            x: self.x + offset.x,
+            y: self.y + offset.y,
+            ..self
+        }
+    }
+
+    /// Grow, or shrink, the rectangle by `factor` around its center.
+    pub fn scale(self, factor: f64) -> Rectangle {
+        Rectangle::with_size(self.w * factor, self.h * factor).center_at(self.center())
+    }
+
     /// Generate a rectangle with the provided size, with its top-left corner
     /// at (0, 0).
     pub fn with_size(w: f64, h: f64) -> Rectangle {
@@ -72,35 +221,853 @@ impl Rectangle {
     }
 
     /// Centers
-    pub fn center_at(self, center: (f64, f64)) -> Rectangle {
+    pub fn center_at(self, center: Vec2) -> Rectangle {
         Rectangle {
-            x: center.0 - self.w / 2.0,
-            y: center.1 - self.h / 2.0,
+            x: center.x - self.w / 2.0,
+            y: center.y - self.h / 2.0,
             ..self
         }
     }
 
     /// Return the center of the rectangle.
-    pub fn center(self) -> (f64, f64) {
+    pub fn center(self) -> Vec2 {
         let x = self.x + self.w / 2.0;
         let y = self.y + self.h / 2.0;
-        (x, y)
+        Vec2::new(x, y)
+    }
+
+    /// Return the region covered by the rectangle while moving in a straight
+    /// line to `to`, e.g. to find what a fast object went through since the
+    /// last frame.
+    pub fn sweep(self, to: Rectangle) -> Polygon {
+        let mut points = Polygon::from_rect(self).points;
+        points.extend(Polygon::from_rect(to).points);
+        Polygon::convex_hull(points)
     }
 }
 
-pub struct MaybeAlive<T> {
-    pub alive: bool,
-    pub value: T,
+/// A disc, e.g. the hitbox of a round asteroid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circle {
+    pub center: Vec2,
+    pub radius: f64,
 }
 
-impl<T> MaybeAlive<T> {
-    // Check whether the value is still alive. If this is the case, then
-    // return `Some(value)`; otherwise, return `None`
-    pub fn as_option(self) -> Option<T> {
-        if self.alive {
-            Some(self.value)
-        } else {
-            None
+impl Circle {
+    pub fn overlaps(&self, other: Circle) -> bool {
+        self.center.distance(other.center) < self.radius + other.radius
+    }
+
+    pub fn overlaps_rect(&self, rect: Rectangle) -> bool {
+        // Compare the distance to the point of the rectangle which is closest
+        // to the center.
+        let closest = Vec2::new(
+            self.center.x.max(rect.x).min(rect.x + rect.w),
+            self.center.y.max(rect.y).min(rect.y + rect.h));
+        self.center.distance(closest) < self.radius
+    }
+
+    /// The smallest rectangle containing the circle.
+    pub fn bounding_box(&self) -> Rectangle {
+        Rectangle::with_size(self.radius * 2.0, self.radius * 2.0).center_at(self.center)
+    }
+}
+
+/// A convex polygon, whose points are listed in order, either clockwise or
+/// counter-clockwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<Vec2>,
+}
+
+impl Polygon {
+    /// The polygon made of the corners of `rect`.
+    pub fn from_rect(rect: Rectangle) -> Polygon {
+        Polygon {
+            points: vec![
+                Vec2::new(rect.x, rect.y),
+                Vec2::new(rect.x + rect.w, rect.y),
+                Vec2::new(rect.x + rect.w, rect.y + rect.h),
+                Vec2::new(rect.x, rect.y + rect.h),
+            ],
+        }
+    }
+
+    /// The smallest convex polygon containing every point of `points`.
+    pub fn convex_hull(mut points: Vec<Vec2>) -> Polygon {
+        points.sort_by(|a, b| (a.x, a.y).partial_cmp(&(b.x, b.y)).unwrap());
+        points.dedup();
+        if points.len() < 3 {
+            return Polygon { points };
+        }
+
+        // Whether going from `a` to `b`, then to `c`, turns clockwise or not
+        // at all, in which case `b` is not part of the hull.
+        let cross = |a: Vec2, b: Vec2, c: Vec2|
+            (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+
+        // Build the lower then the upper half of the hull.
+        let mut hull: Vec<Vec2> = vec![];
+        for pass in 0..2 {
+            let start = hull.len();
+            let ordered: Box<dyn Iterator<Item = &Vec2>> =
+                if pass == 0 { Box::new(points.iter()) }
+                else { Box::new(points.iter().rev()) };
+
+            for &point in ordered {
+                while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+                    hull.pop();
+                }
+                hull.push(point);
+            }
+
+            // The last point is the first of the other half.
+            hull.pop();
+        }
+
+        Polygon { points: hull }
+    }
+
+    /// The directions perpendicular to the edges of the polygon. Two convex
+    /// shapes do not overlap if and only if their projections on one of their
+    /// axes are disjoint.
+    fn axes(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let next = self.points.iter().cycle().skip(1);
+        self.points.iter().zip(next)
+            .map(|(&a, &b)| Vec2::new(a.y - b.y, b.x - a.x))
+    }
+
+    /// The range covered by the polygon along `axis`.
+    fn project(&self, axis: Vec2) -> (f64, f64) {
+        self.points.iter()
+            .map(|&point| point.dot(axis))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), p| (min.min(p), max.max(p)))
+    }
+
+    pub fn overlaps(&self, other: &Polygon) -> bool {
+        self.axes().chain(other.axes()).all(|axis| {
+            let (min_a, max_a) = self.project(axis);
+            let (min_b, max_b) = other.project(axis);
+            min_a < max_b && min_b < max_a
+        })
+    }
+
+    pub fn overlaps_circle(&self, circle: Circle) -> bool {
+        // Besides the edges, the circle may only be separated from the polygon
+        // along the line joining its center to the closest point.
+        let closest = self.points.iter()
+            .cloned()
+            .min_by(|a, b| {
+                let da = a.distance(circle.center);
+                let db = b.distance(circle.center);
+                da.partial_cmp(&db).unwrap()
+            });
+        let towards_center = closest.map(|point| circle.center - point);
+
+        self.axes().chain(towards_center).all(|axis| {
+            let length = axis.length();
+            if length == 0.0 {
+                return true;
+            }
+
+            let (min, max) = self.project(axis);
+            let center = circle.center.dot(axis);
+            let radius = circle.radius * length;
+            min < center + radius && center - radius < max
+        })
+    }
+
+    /// The smallest rectangle containing the polygon.
+    pub fn bounding_box(&self) -> Rectangle {
+        let (min_x, max_x) = self.project(Vec2::new(1.0, 0.0));
+        let (min_y, max_y) = self.project(Vec2::new(0.0, 1.0));
+        Rectangle { x: min_x, y: min_y, w: max_x - min_x, h: max_y - min_y }
+    }
+}
+
+/// The region in which an entity may collide with others.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Rect(Rectangle),
+    Circle(Circle),
+    Polygon(Polygon),
+}
+
+impl Shape {
+    pub fn overlaps(&self, other: &Shape) -> bool {
+        // Most shapes are far apart, which their bounding boxes tell cheaply.
+        if !self.bounding_box().overlaps(other.bounding_box()) {
+            return false;
+        }
+
+        match (self, other) {
+            (Shape::Rect(a), Shape::Rect(b)) => a.overlaps(*b),
+            (Shape::Circle(a), Shape::Circle(b)) => a.overlaps(*b),
+            (Shape::Polygon(a), Shape::Polygon(b)) => a.overlaps(b),
+
+            (Shape::Circle(circle), Shape::Rect(rect)) |
+            (Shape::Rect(rect), Shape::Circle(circle)) => circle.overlaps_rect(*rect),
+
+            (Shape::Polygon(polygon), Shape::Rect(rect)) |
+            (Shape::Rect(rect), Shape::Polygon(polygon)) => polygon.overlaps(&Polygon::from_rect(*rect)),
+
+            (Shape::Polygon(polygon), Shape::Circle(circle)) |
+            (Shape::Circle(circle), Shape::Polygon(polygon)) => polygon.overlaps_circle(*circle),
+        }
+    }
+
+    /// The same shape, moved by `offset`.
+    pub fn translate(&self, offset: Vec2) -> Shape {
+        match *self {
+            Shape::Rect(rect) => Shape::Rect(rect.translate(offset)),
+            Shape::Circle(circle) => Shape::Circle(Circle { center: circle.center + offset, ..circle }),
+            Shape::Polygon(ref polygon) => Shape::Polygon(Polygon {
+                points: polygon.points.iter().map(|&point| point + offset).collect(),
+            }),
+        }
+    }
+
+    /// The smallest rectangle containing the shape.
+    pub fn bounding_box(&self) -> Rectangle {
+        match *self {
+            Shape::Rect(rect) => rect,
+            Shape::Circle(circle) => circle.bounding_box(),
+            Shape::Polygon(ref polygon) => polygon.bounding_box(),
+        }
+    }
+
+    /// The outline of the shape, as a closed sequence of points, e.g. to
+    /// render it while debugging. Circles are approximated.
+    pub fn outline(&self) -> Vec<Vec2> {
+        match *self {
+            Shape::Rect(rect) => Polygon::from_rect(rect).points,
+            Shape::Circle(circle) => (0..CIRCLE_OUTLINE_POINTS)
+                .map(|i| {
+                    let angle = i as f64 / CIRCLE_OUTLINE_POINTS as f64 * 360.0;
+                    circle.center + Vec2::new(circle.radius, 0.0).rotate(angle)
+                })
+                .collect(),
+            Shape::Polygon(ref polygon) => polygon.points.clone(),
+        }
+    }
+}
+
+/// Which pixels of an image are opaque enough to collide with others.
+pub struct Mask {
+    w: usize,
+    h: usize,
+    opaque: Vec<bool>,
+}
+
+/// Where a region of a mask ends up on the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaskPlacement {
+    /// The region of the mask, in pixels of its image.
+    pub region: Rectangle,
+
+    /// The region of the screen to which `region` is stretched.
+    pub dest: Rectangle,
+
+    /// The rotation, in degrees, clockwise, around the center of `dest`.
+    pub angle: f64,
+
+    /// Whether the region is mirrored, left to right and top to bottom, before
+    /// it is rotated, as when rendered with the same `RenderOptions`.
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl MaskPlacement {
+    /// The smallest rectangle of the screen containing the placed region.
+    fn bounding_box(&self) -> Rectangle {
+        let center = self.dest.center();
+
+        Polygon {
+            points: Polygon::from_rect(self.dest).points.into_iter()
+                .map(|point| center + (point - center).rotate(self.angle))
+                .collect(),
+        }.bounding_box()
+    }
+
+    /// The pixel of the mask shown at `point` of the screen, if the point is
+    /// covered by the region.
+    fn to_mask(self, point: Vec2) -> Option<(usize, usize)> {
+        // Where the point would be if the region were not rotated.
+        let center = self.dest.center();
+        let point = center + (point - center).rotate(-self.angle);
+        if !self.dest.contains_point(point) {
+            return None;
+        }
+
+        // The position of the point in the region, from 0 to 1.
+        let u = (point.x - self.dest.x) / self.dest.w;
+        let v = (point.y - self.dest.y) / self.dest.h;
+        let (w, h) = (self.region.w as usize, self.region.h as usize);
+        let (mut x, mut y) = ((u * self.region.w) as usize, (v * self.region.h) as usize);
+        if self.flip_horizontal {
+            x = w.saturating_sub(x + 1);
         }
+        if self.flip_vertical {
+            y = h.saturating_sub(y + 1);
+        }
+
+        Some((self.region.x as usize + x, self.region.y as usize + y))
     }
-}
\ No newline at end of file
+}
+
+/// The opacity, from 0 to 255, above which a pixel collides.
+const MASK_ALPHA_THRESHOLD: u8 = 128;
+
+impl Mask {
+    /// Computes the mask of an image of the given size, from its RGBA pixels,
+    /// whose rows are `pitch` bytes apart.
+    pub fn from_rgba(w: usize, h: usize, pitch: usize, pixels: &[u8]) -> Mask {
+        let mut opaque = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                opaque.push(pixels[y * pitch + x * 4 + 3] > MASK_ALPHA_THRESHOLD);
+            }
+        }
+
+        Mask { w, h, opaque }
+    }
+
+    /// Whether `pixel` is opaque. Points which are not covered by a region, or
+    /// outside of the image, are not.
+    fn is_opaque(&self, pixel: Option<(usize, usize)>) -> bool {
+        match pixel {
+            Some((x, y)) => x < self.w && y < self.h && self.opaque[y * self.w + x],
+            None => false,
+        }
+    }
+
+    /// Whether an opaque pixel of `self`, placed as `placement`, covers an
+    /// opaque pixel of `other`, placed as `other_placement`. Only the pixels
+    /// of the screen covered by both are tested, so this is best used once
+    /// cheaper tests found that the two may overlap.
+    pub fn overlaps(&self, placement: MaskPlacement, other: &Mask, other_placement: MaskPlacement) -> bool {
+        let both = match placement.bounding_box().intersection(other_placement.bounding_box()) {
+            Some(both) => both,
+            None => return false,
+        };
+
+        let (x_min, x_max) = (both.x.floor(), (both.x + both.w).ceil());
+        let (y_min, y_max) = (both.y.floor(), (both.y + both.h).ceil());
+
+        let mut y = y_min;
+        while y < y_max {
+            let mut x = x_min;
+            while x < x_max {
+                // Sample the center of every pixel of the screen.
+                let point = Vec2::new(x + 0.5, y + 0.5);
+                if self.is_opaque(placement.to_mask(point)) &&
+                   other.is_opaque(other_placement.to_mask(point)) {
+                    return true;
+                }
+                x += 1.0;
+            }
+            y += 1.0;
+        }
+
+        false
+    }
+}
+
+/// Buckets values by the cells of a uniform grid covered by their bounding
+/// boxes, so that the values near a region can be found without testing all of
+/// them. It is meant to be filled anew every frame.
+pub struct SpatialHash<T> {
+    cell_size: f64,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    items: Vec<(Rectangle, T)>,
+}
+
+impl<T: Copy> SpatialHash<T> {
+    /// Creates an empty grid whose cells are `cell_size` pixels wide and high.
+    /// It works best when cells are about the size of the largest values.
+    pub fn new(cell_size: f64) -> SpatialHash<T> {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            items: vec![],
+        }
+    }
+
+    /// The range of cells covered by `rect`, inclusive.
+    fn cells_of(&self, rect: Rectangle) -> ((i32, i32), (i32, i32)) {
+        let cell = |v: f64| (v / self.cell_size).floor() as i32;
+        ((cell(rect.x), cell(rect.x + rect.w)), (cell(rect.y), cell(rect.y + rect.h)))
+    }
+
+    pub fn insert(&mut self, rect: Rectangle, value: T) {
+        let index = self.items.len();
+        self.items.push((rect, value));
+
+        let ((x_min, x_max), (y_min, y_max)) = self.cells_of(rect);
+        for x in x_min..=x_max {
+            for y in y_min..=y_max {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    /// Returns the values whose bounding box overlaps `rect`, in the order in
+    /// which they were inserted.
+    pub fn query(&self, rect: Rectangle) -> Vec<T> {
+        let ((x_min, x_max), (y_min, y_max)) = self.cells_of(rect);
+        let mut found = vec![];
+        for x in x_min..=x_max {
+            for y in y_min..=y_max {
+                if let Some(indices) = self.cells.get(&(x, y)) {
+                    found.extend_from_slice(indices);
+                }
+            }
+        }
+
+        // Values covering several cells are found once per cell.
+        found.sort_unstable();
+        found.dedup();
+        found.into_iter()
+            .filter(|&index| self.items[index].0.overlaps(rect))
+            .map(|index| self.items[index].1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn rect(x: f64, y: f64, w: f64, h: f64) -> Shape {
+        Shape::Rect(Rectangle { x, y, w, h })
+    }
+
+    fn circle(x: f64, y: f64, radius: f64) -> Shape {
+        Shape::Circle(Circle { center: Vec2::new(x, y), radius })
+    }
+
+    fn polygon(points: &[(f64, f64)]) -> Shape {
+        Shape::Polygon(Polygon { points: points.iter().map(|&(x, y)| Vec2::new(x, y)).collect() })
+    }
+
+    /// A diamond whose corners are 10 pixels away from its center, at (0, 0).
+    fn diamond() -> Shape {
+        polygon(&[(0.0, -10.0), (10.0, 0.0), (0.0, 10.0), (-10.0, 0.0)])
+    }
+
+    /// Checks both orders, which must agree.
+    fn overlap(a: &Shape, b: &Shape) -> bool {
+        assert_eq!(a.overlaps(b), b.overlaps(a), "{:?} and {:?}", a, b);
+        a.overlaps(b)
+    }
+
+    #[test]
+    fn rects_overlap_unless_they_only_touch() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        assert!(overlap(&a, &rect(5.0, 5.0, 10.0, 10.0)));
+        assert!(overlap(&a, &rect(2.0, 2.0, 2.0, 2.0)));
+        assert!(!overlap(&a, &rect(10.0, 0.0, 10.0, 10.0)));
+        assert!(!overlap(&a, &rect(20.0, 20.0, 10.0, 10.0)));
+    }
+
+    #[test]
+    fn circles_overlap_unless_they_only_touch() {
+        let a = circle(0.0, 0.0, 5.0);
+        assert!(overlap(&a, &circle(8.0, 0.0, 5.0)));
+        assert!(!overlap(&a, &circle(10.0, 0.0, 5.0)));
+        assert!(!overlap(&a, &circle(30.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn circle_and_rect() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        assert!(overlap(&a, &circle(12.0, 5.0, 3.0)));
+        assert!(!overlap(&a, &circle(13.0, 5.0, 3.0)));
+
+        // Near a corner, the rounded edge of the circle misses it.
+        assert!(!overlap(&a, &circle(12.5, 12.5, 3.0)));
+        assert!(overlap(&a, &circle(11.5, 11.5, 3.0)));
+    }
+
+    #[test]
+    fn polygons_are_separated_by_their_edges() {
+        let a = diamond();
+        assert!(overlap(&a, &diamond().translate(Vec2::new(15.0, 0.0))));
+        assert!(!overlap(&a, &diamond().translate(Vec2::new(20.0, 0.0))));
+
+        // The bounding boxes overlap, but an edge separates the diamonds.
+        assert!(!overlap(&a, &diamond().translate(Vec2::new(11.0, 11.0))));
+    }
+
+    #[test]
+    fn polygon_and_rect() {
+        let a = diamond();
+        assert!(overlap(&a, &rect(5.0, -2.0, 10.0, 4.0)));
+        assert!(!overlap(&a, &rect(10.0, -2.0, 10.0, 4.0)));
+
+        // The corner of the rect is in the bounding box of the diamond only.
+        assert!(!overlap(&a, &rect(6.0, 6.0, 10.0, 10.0)));
+    }
+
+    #[test]
+    fn polygon_and_circle() {
+        let a = diamond();
+        assert!(overlap(&a, &circle(12.0, 0.0, 3.0)));
+        assert!(!overlap(&a, &circle(13.0, 0.0, 3.0)));
+
+        // The circle faces an edge, rather than a corner, of the diamond.
+        assert!(overlap(&a, &circle(6.0, 6.0, 2.0)));
+        assert!(!overlap(&a, &circle(8.0, 8.0, 2.0)));
+    }
+
+    #[test]
+    fn a_shape_inside_of_another_overlaps_it() {
+        let a = diamond();
+        assert!(overlap(&a, &circle(0.0, 0.0, 1.0)));
+        assert!(overlap(&a, &rect(-1.0, -1.0, 2.0, 2.0)));
+        assert!(overlap(&circle(0.0, 0.0, 50.0), &a));
+    }
+
+    /// The points of `polygon`, sorted, to compare them regardless of where
+    /// the outline starts.
+    fn corners(polygon: &Polygon) -> Vec<(f64, f64)> {
+        let mut corners: Vec<_> = polygon.points.iter().map(|point| (point.x, point.y)).collect();
+        corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        corners
+    }
+
+    fn hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        corners(&Polygon::convex_hull(points.iter().map(|&(x, y)| Vec2::new(x, y)).collect()))
+    }
+
+    #[test]
+    fn hull_of_a_point() {
+        assert_eq!(hull(&[(1.0, 2.0)]), vec![(1.0, 2.0)]);
+        assert_eq!(hull(&[(1.0, 2.0), (1.0, 2.0), (1.0, 2.0)]), vec![(1.0, 2.0)]);
+    }
+
+    #[test]
+    fn hull_of_collinear_points_is_a_segment() {
+        assert_eq!(hull(&[(2.0, 2.0), (0.0, 0.0), (3.0, 3.0), (1.0, 1.0)]), vec![(0.0, 0.0), (3.0, 3.0)]);
+        assert_eq!(hull(&[(0.0, 5.0), (0.0, 1.0), (0.0, 3.0)]), vec![(0.0, 1.0), (0.0, 5.0)]);
+    }
+
+    #[test]
+    fn hull_drops_inner_points_and_points_on_edges() {
+        let points = [(0.0, 0.0), (5.0, 5.0), (10.0, 0.0), (5.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 10.0)];
+        assert_eq!(hull(&points), vec![(0.0, 0.0), (0.0, 10.0), (10.0, 0.0), (10.0, 10.0)]);
+    }
+
+    #[test]
+    fn hull_is_in_order() {
+        let hull = Polygon::convex_hull(vec![
+            Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0), Vec2::new(10.0, 0.0), Vec2::new(0.0, 10.0)]);
+
+        // Consecutive corners are joined by edges, never diagonals.
+        let next = hull.points.iter().cycle().skip(1);
+        for (&a, &b) in hull.points.iter().zip(next) {
+            assert_eq!(a.distance(b), 10.0, "{:?}", hull);
+        }
+    }
+
+    #[test]
+    fn sweep_without_moving_is_the_rect() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 10.0 };
+        assert_eq!(corners(&a.sweep(a)), corners(&Polygon::from_rect(a)));
+    }
+
+    #[test]
+    fn sweep_along_an_axis_is_a_rect() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 10.0 };
+        let to = a.translate(Vec2::new(20.0, 0.0));
+        assert_eq!(corners(&a.sweep(to)), corners(&Polygon::from_rect(a.union(to))));
+    }
+
+    #[test]
+    fn sweep_along_a_diagonal_cuts_the_corners() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 10.0 };
+        let swept = a.sweep(a.translate(Vec2::new(20.0, 20.0)));
+        assert_eq!(corners(&swept), vec![
+            (0.0, 0.0), (0.0, 10.0), (10.0, 0.0), (20.0, 30.0), (30.0, 20.0), (30.0, 30.0)]);
+    }
+
+    #[test]
+    fn sweep_hits_what_was_passed_through() {
+        // A bullet which jumped over a thin wall between two frames.
+        let bullet = Rectangle { x: 0.0, y: 0.0, w: 4.0, h: 4.0 };
+        let swept = Shape::Polygon(bullet.sweep(bullet.translate(Vec2::new(40.0, 40.0))));
+
+        assert!(overlap(&swept, &rect(20.0, 0.0, 2.0, 40.0)));
+        assert!(!overlap(&swept, &rect(30.0, 0.0, 2.0, 10.0)));
+
+        // The path touches the wall without entering it.
+        assert!(!overlap(&swept, &rect(4.0, -10.0, 2.0, 10.0)));
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(actual.distance(expected) < 1e-9, "{:?} is not {:?}", actual, expected);
+    }
+
+    #[test]
+    fn vec2_operators() {
+        let a = Vec2::new(1.0, 2.0);
+        let b = Vec2::new(3.0, -4.0);
+        assert_eq!(a + b, Vec2::new(4.0, -2.0));
+        assert_eq!(a - b, Vec2::new(-2.0, 6.0));
+        assert_eq!(a * 2.0, Vec2::new(2.0, 4.0));
+        assert_eq!(b / 2.0, Vec2::new(1.5, -2.0));
+        assert_eq!(-a, Vec2::new(-1.0, -2.0));
+        assert_eq!(a.dot(b), -5.0);
+        assert_eq!(b.length(), 5.0);
+
+        let mut c = a;
+        c += b;
+        assert_eq!(c, a + b);
+        c -= b;
+        assert_eq!(c, a);
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(Vec2::new(3.0, -4.0).normalize(), Vec2::new(0.6, -0.8));
+        assert_eq!(Vec2::default().normalize(), Vec2::default());
+    }
+
+    #[test]
+    fn lerp() {
+        let a = Vec2::new(0.0, 10.0);
+        let b = Vec2::new(20.0, 0.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 0.25), Vec2::new(5.0, 7.5));
+        assert_eq!(a.lerp(b, 1.0), b);
+    }
+
+    #[test]
+    fn rotate_turns_clockwise_on_the_screen() {
+        let right = Vec2::new(1.0, 0.0);
+        assert_close(right.rotate(90.0), Vec2::new(0.0, 1.0));
+        assert_close(right.rotate(180.0), Vec2::new(-1.0, 0.0));
+        assert_close(right.rotate(-90.0), Vec2::new(0.0, -1.0));
+        assert_close(Vec2::new(2.0, 3.0).rotate(360.0), Vec2::new(2.0, 3.0));
+    }
+
+    #[test]
+    fn distance() {
+        let a = Vec2::new(1.0, 1.0);
+        assert_eq!(a.distance(Vec2::new(4.0, 5.0)), 5.0);
+        assert_eq!(Vec2::new(4.0, 5.0).distance(a), 5.0);
+        assert_eq!(a.distance(a), 0.0);
+    }
+
+    #[test]
+    fn intersection() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 10.0 };
+        assert_eq!(a.intersection(Rectangle { x: 20.0, y: 0.0, w: 10.0, h: 10.0 }), None);
+        assert_eq!(a.intersection(Rectangle { x: 10.0, y: 0.0, w: 10.0, h: 10.0 }), None);
+        assert_eq!(a.intersection(Rectangle { x: 5.0, y: -5.0, w: 10.0, h: 10.0 }),
+            Some(Rectangle { x: 5.0, y: 0.0, w: 5.0, h: 5.0 }));
+
+        let inner = Rectangle { x: 2.0, y: 3.0, w: 4.0, h: 5.0 };
+        assert_eq!(a.intersection(inner), Some(inner));
+        assert_eq!(inner.intersection(a), Some(inner));
+    }
+
+    #[test]
+    fn union() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 10.0 };
+        let b = Rectangle { x: 20.0, y: -5.0, w: 5.0, h: 5.0 };
+        assert_eq!(a.union(b), Rectangle { x: 0.0, y: -5.0, w: 25.0, h: 15.0 });
+        assert_eq!(b.union(a), a.union(b));
+        assert_eq!(a.union(Rectangle { x: 2.0, y: 2.0, w: 2.0, h: 2.0 }), a);
+    }
+
+    #[test]
+    fn translate_and_scale() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 20.0 };
+        assert_eq!(a.translate(Vec2::new(5.0, -5.0)), Rectangle { x: 5.0, y: -5.0, w: 10.0, h: 20.0 });
+        assert_eq!(a.scale(2.0), Rectangle { x: -5.0, y: -10.0, w: 20.0, h: 40.0 });
+        assert_eq!(a.scale(0.5), Rectangle { x: 2.5, y: 5.0, w: 5.0, h: 10.0 });
+        assert_eq!(a.scale(2.0).center(), a.center());
+    }
+
+    #[test]
+    fn contains_point_on_the_edges() {
+        let a = Rectangle { x: 0.0, y: 0.0, w: 10.0, h: 10.0 };
+        assert!(a.contains_point(Vec2::new(0.0, 0.0)));
+        assert!(a.contains_point(Vec2::new(5.0, 0.0)));
+        assert!(a.contains_point(Vec2::new(0.0, 5.0)));
+        assert!(a.contains_point(Vec2::new(9.99, 9.99)));
+
+        // The right and bottom edges belong to the neighbouring rectangles.
+        assert!(!a.contains_point(Vec2::new(10.0, 5.0)));
+        assert!(!a.contains_point(Vec2::new(5.0, 10.0)));
+        assert!(!a.contains_point(Vec2::new(10.0, 10.0)));
+        assert!(!a.contains_point(Vec2::new(-0.01, 5.0)));
+    }
+
+    fn query(hash: &SpatialHash<char>, x: f64, y: f64, w: f64, h: f64) -> Vec<char> {
+        hash.query(Rectangle { x, y, w, h })
+    }
+
+    #[test]
+    fn values_covering_several_cells_are_found_once() {
+        let mut hash = SpatialHash::new(10.0);
+        hash.insert(Rectangle { x: 5.0, y: 5.0, w: 30.0, h: 30.0 }, 'a');
+        hash.insert(Rectangle { x: 12.0, y: 12.0, w: 2.0, h: 2.0 }, 'b');
+
+        assert_eq!(query(&hash, 0.0, 0.0, 50.0, 50.0), vec!['a', 'b']);
+        assert_eq!(query(&hash, 30.0, 30.0, 2.0, 2.0), vec!['a']);
+        assert_eq!(query(&hash, 40.0, 0.0, 10.0, 10.0), vec![]);
+    }
+
+    #[test]
+    fn spatial_hash_works_at_negative_coordinates() {
+        let mut hash = SpatialHash::new(10.0);
+        hash.insert(Rectangle { x: -25.0, y: -25.0, w: 10.0, h: 10.0 }, 'a');
+        hash.insert(Rectangle { x: -5.0, y: -5.0, w: 10.0, h: 10.0 }, 'b');
+        hash.insert(Rectangle { x: 20.0, y: 20.0, w: 5.0, h: 5.0 }, 'c');
+
+        assert_eq!(query(&hash, -30.0, -30.0, 10.0, 10.0), vec!['a']);
+        assert_eq!(query(&hash, -1.0, -1.0, 2.0, 2.0), vec!['b']);
+        assert_eq!(query(&hash, -3.0, 1.0, 1.0, 1.0), vec!['b']);
+        assert_eq!(query(&hash, -100.0, -100.0, 200.0, 200.0), vec!['a', 'b', 'c']);
+    }
+
+    #[test]
+    fn spatial_hash_queries_across_cell_edges() {
+        let mut hash = SpatialHash::new(10.0);
+        hash.insert(Rectangle { x: 0.0, y: 0.0, w: 9.9, h: 5.0 }, 'a');
+        hash.insert(Rectangle { x: 10.05, y: 0.0, w: 5.0, h: 5.0 }, 'b');
+        hash.insert(Rectangle { x: 0.0, y: 10.0, w: 5.0, h: 5.0 }, 'c');
+
+        // Queries in the neighbouring cell reach back over the edge.
+        assert_eq!(query(&hash, 9.8, 1.0, 0.5, 1.0), vec!['a', 'b']);
+        assert_eq!(query(&hash, 9.95, 1.0, 0.05, 1.0), vec![]);
+
+        // Touching, as on the edge between two cells, is not overlapping.
+        assert_eq!(query(&hash, 0.0, 5.0, 5.0, 5.0), vec![]);
+        assert_eq!(query(&hash, 0.0, 9.0, 5.0, 1.5), vec!['c']);
+    }
+
+    #[test]
+    fn spatial_hash_finds_what_testing_every_pair_finds() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut random_rect = |max_size: f64| Rectangle {
+            x: rng.gen_range(-500.0..500.0),
+            y: rng.gen_range(-500.0..500.0),
+            w: rng.gen_range(0.0..max_size),
+            h: rng.gen_range(0.0..max_size),
+        };
+
+        let rects: Vec<Rectangle> = (0..300).map(|_| random_rect(80.0)).collect();
+        let mut hash = SpatialHash::new(50.0);
+        for (i, &rect) in rects.iter().enumerate() {
+            hash.insert(rect, i);
+        }
+
+        for _ in 0..100 {
+            let region = random_rect(200.0);
+            let expected: Vec<usize> = (0..rects.len()).filter(|&i| rects[i].overlaps(region)).collect();
+            assert_eq!(hash.query(region), expected);
+        }
+    }
+
+    /// A mask of the given size, whose opaque pixels are marked `#` in `rows`.
+    fn mask(rows: &[&str]) -> Mask {
+        let (w, h) = (rows[0].len(), rows.len());
+        let pixels: Vec<u8> = rows.iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|c| [255, 255, 255, if c == '#' { 255 } else { 0 }])
+            .collect();
+        Mask::from_rgba(w, h, w * 4, &pixels)
+    }
+
+    /// Where a whole mask of the given size is placed at `dest`.
+    fn placed(w: f64, h: f64, dest: Rectangle) -> MaskPlacement {
+        MaskPlacement {
+            region: Rectangle::with_size(w, h),
+            dest,
+            angle: 0.0,
+            flip_horizontal: false,
+            flip_vertical: false,
+        }
+    }
+
+    /// Whether an opaque pixel at `(x, y)` hits `target`, placed as `placement`.
+    fn hits(target: &Mask, placement: MaskPlacement, x: f64, y: f64) -> bool {
+        let dot = Rectangle { x, y, w: 1.0, h: 1.0 };
+        target.overlaps(placement, &mask(&["#"]), placed(1.0, 1.0, dot))
+    }
+
+    #[test]
+    fn only_opaque_pixels_overlap() {
+        let corner = mask(&["#.", ".."]);
+        let at_origin = placed(2.0, 2.0, Rectangle::with_size(2.0, 2.0));
+        assert!(hits(&corner, at_origin, 0.0, 0.0));
+        assert!(!hits(&corner, at_origin, 1.0, 1.0));
+        assert!(!hits(&corner, at_origin, 1.0, 0.0));
+        assert!(!hits(&corner, at_origin, 5.0, 5.0));
+
+        // Stretched to twice its size.
+        let stretched = placed(2.0, 2.0, Rectangle::with_size(4.0, 4.0));
+        assert!(hits(&corner, stretched, 1.0, 1.0));
+        assert!(!hits(&corner, stretched, 2.0, 2.0));
+    }
+
+    #[test]
+    fn rotated_masks_overlap_where_they_are_rendered() {
+        // A bar, 10 pixels wide and 2 high, centered on (5, 5).
+        let bar = mask(&["##########", "##########"]);
+        let flat = placed(10.0, 2.0, Rectangle { x: 0.0, y: 4.0, w: 10.0, h: 2.0 });
+        assert!(hits(&bar, flat, 0.0, 4.0));
+        assert!(!hits(&bar, flat, 4.0, 0.0));
+
+        // Upright once turned by 90 degrees.
+        let upright = MaskPlacement { angle: 90.0, ..flat };
+        assert!(!hits(&bar, upright, 0.0, 4.0));
+        assert!(hits(&bar, upright, 4.0, 0.0));
+        assert!(hits(&bar, upright, 5.0, 9.0));
+        assert!(!hits(&bar, upright, 6.0, 9.0));
+    }
+
+    #[test]
+    fn flipped_masks_overlap_where_they_are_rendered() {
+        let corner = mask(&["#.", ".."]);
+        let at_origin = placed(2.0, 2.0, Rectangle::with_size(2.0, 2.0));
+
+        let mirrored = MaskPlacement { flip_horizontal: true, ..at_origin };
+        assert!(!hits(&corner, mirrored, 0.0, 0.0));
+        assert!(hits(&corner, mirrored, 1.0, 0.0));
+
+        let upside_down = MaskPlacement { flip_vertical: true, ..at_origin };
+        assert!(hits(&corner, upside_down, 0.0, 1.0));
+
+        let both = MaskPlacement { flip_horizontal: true, flip_vertical: true, ..at_origin };
+        assert!(hits(&corner, both, 1.0, 1.0));
+        assert!(!hits(&corner, both, 0.0, 0.0));
+
+        // Flipping happens before the rotation: a bar with an opaque left end,
+        // mirrored and turned clockwise, has it at the bottom.
+        let bar = mask(&["#.........", "#........."]);
+        let flat = placed(10.0, 2.0, Rectangle { x: 0.0, y: 4.0, w: 10.0, h: 2.0 });
+        let turned = MaskPlacement { angle: 90.0, flip_horizontal: true, ..flat };
+        assert!(hits(&bar, turned, 4.0, 9.0));
+        assert!(!hits(&bar, turned, 4.0, 0.0));
+    }
+
+    #[test]
+    fn masks_of_atlas_regions_only_use_their_region() {
+        // Two frames side by side, the right one mirrored: its edge must not
+        // sample the left one.
+        let atlas = mask(&["#.", "#."]);
+        let right = MaskPlacement {
+            region: Rectangle { x: 1.0, y: 0.0, w: 1.0, h: 2.0 },
+            flip_horizontal: true,
+            ..placed(1.0, 2.0, Rectangle::with_size(4.0, 4.0))
+        };
+        assert!(!hits(&atlas, right, 0.0, 0.0));
+        assert!(!hits(&atlas, right, 3.0, 3.0));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::phi::gfx::BitmapFont;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect as SdlRect;
+use sdl2::render::{BlendMode, WindowCanvas};
+use std::collections::VecDeque;
+
+/// The font used by the overlay. Its glyphs are upper case only.
+const DEBUG_FONT_PATH: &str = "assets/pixel_font.fnt";
+
+/// The number of frames shown in the frame time graph.
+const GRAPH_FRAMES: usize = 120;
+
+/// The frame time, in seconds, shown at the top of the graph. Slower frames
+/// are clamped to it.
+const GRAPH_MAX_TIME: f64 = 1.0 / 20.0;
+
+/// The frame time, in seconds, which the game tries to stay under.
+const GRAPH_TARGET_TIME: f64 = 1.0 / 60.0;
+
+const GRAPH_H: f64 = 60.0;
+const MARGIN: f64 = 10.0;
+
+/// Statistics shown on top of the current view, for debugging purposes.
+///
+/// Phi measures the framerate itself, while views describe their own state by
+/// adding lines of text every frame and by rendering whatever else they need,
+/// such as collision boxes, while the overlay is `visible`.
+pub struct DebugOverlay {
+    pub visible: bool,
+    font: Option<BitmapFont>,
+
+    /// How long the latest frames took, the most recent last, in seconds.
+    frame_times: VecDeque<f64>,
+
+    /// The number of frames rendered during the last full second.
+    fps: u16,
+    frames: u16,
+    second: f64,
+
+    /// The lines added by the view since the overlay was last rendered.
+    lines: Vec<String>,
+}
+
+impl DebugOverlay {
+    pub fn new(renderer: &WindowCanvas) -> DebugOverlay {
+        DebugOverlay {
+            visible: false,
+            // The overlay is still useful without its text.
+            font: BitmapFont::load(renderer, DEBUG_FONT_PATH).ok(),
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+            fps: 0,
+            frames: 0,
+            second: 0.0,
+            lines: vec![],
+        }
+    }
+
+    /// Record that a frame took `elapsed` seconds.
+    pub fn record_frame(&mut self, elapsed: f64) {
+        if self.frame_times.len() == GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(elapsed);
+
+        self.frames += 1;
+        self.second += elapsed;
+        if self.second >= 1.0 {
+            self.fps = self.frames;
+            self.frames = 0;
+            self.second -= 1.0;
+        }
+    }
+
+    /// The number of frames rendered during the last full second.
+    pub fn fps(&self) -> u16 {
+        self.fps
+    }
+
+    /// Show `text` in the overlay during the current frame. It is ignored
+    /// while the overlay is hidden.
+    pub fn line(&mut self, text: String) {
+        if self.visible {
+            self.lines.push(text);
+        }
+    }
+
+    /// Render the statistics in the top-left corner of the window, then forget
+    /// the lines added during this frame.
+    pub fn render(&mut self, renderer: &mut WindowCanvas) {
+        let old_blend = renderer.blend_mode();
+        renderer.set_blend_mode(BlendMode::Blend);
+
+        // The frame time graph, with one bar per frame, in red when the frame
+        // missed the target.
+        let bar_w = 2.0;
+        let graph_w = GRAPH_FRAMES as f64 * bar_w;
+        renderer.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        renderer.fill_rect(SdlRect::new(MARGIN as i32, MARGIN as i32, graph_w as u32, GRAPH_H as u32)).unwrap();
+
+        for (i, &time) in self.frame_times.iter().enumerate() {
+            let h = (time.min(GRAPH_MAX_TIME) / GRAPH_MAX_TIME * GRAPH_H).max(1.0);
+            let color =
+                if time > GRAPH_TARGET_TIME * 1.1 { Color::RGB(230, 60, 60) }
+                else { Color::RGB(60, 200, 60) };
+
+            renderer.set_draw_color(color);
+            renderer.fill_rect(SdlRect::new(
+                (MARGIN + i as f64 * bar_w) as i32,
+                (MARGIN + GRAPH_H - h) as i32,
+                bar_w as u32,
+                h as u32)).unwrap();
+        }
+
+        let target_y = MARGIN + GRAPH_H - GRAPH_TARGET_TIME / GRAPH_MAX_TIME * GRAPH_H;
+        renderer.set_draw_color(Color::RGBA(255, 255, 255, 120));
+        renderer.fill_rect(SdlRect::new(MARGIN as i32, target_y as i32, graph_w as u32, 1)).unwrap();
+
+        if let Some(ref font) = self.font {
+            let last = self.frame_times.back().cloned().unwrap_or(0.0);
+            let mut text = format!("FPS {}  FRAME {:.1} MS", self.fps, last * 1000.0);
+            for line in &self.lines {
+                text.push('\n');
+                text.push_str(line);
+            }
+
+            font.render(renderer, &text, MARGIN, MARGIN * 2.0 + GRAPH_H, 2);
+        }
+
+        renderer.set_blend_mode(old_blend);
+        self.lines.clear();
+    }
+}
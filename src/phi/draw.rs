@@ -0,0 +1,170 @@
+use crate::phi::camera::Camera;
+use crate::phi::data::{Rectangle, Shape};
+use crate::phi::gfx::{RenderOptions, Renderable};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point as SdlPoint, Rect as SdlRect};
+use sdl2::render::WindowCanvas;
+
+/// The layers in which things are rendered, from the back to the front.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Background,
+    Scene,
+    Foreground,
+
+    /// Rendered in window coordinates, regardless of the camera.
+    Hud,
+
+    /// Rendered on top of everything else, through the camera, e.g. for
+    /// collision boxes.
+    Debug,
+}
+
+impl Layer {
+    /// Whether the camera transform applies to the layer.
+    fn follows_camera(self) -> bool {
+        self != Layer::Hud
+    }
+}
+
+enum DrawCommand<'a> {
+    Renderable(&'a dyn Renderable, Rectangle, RenderOptions),
+    Fill(Rectangle, Color),
+    Outline(Rectangle, Color),
+    Lines(Vec<SdlPoint>, Color),
+    Custom(Box<dyn FnOnce(&mut WindowCanvas) + 'a>),
+}
+
+struct DrawItem<'a> {
+    layer: Layer,
+    z: i32,
+    command: DrawCommand<'a>,
+}
+
+/// Collects what a view wants to render during a frame, so that it can be
+/// rendered in the order given by the layers rather than the order in which
+/// it was submitted.
+///
+/// Within a layer, items are rendered by increasing `z`, then in the order in
+/// which they were submitted.
+pub struct DrawList<'a> {
+    items: Vec<DrawItem<'a>>,
+}
+
+impl<'a> DrawList<'a> {
+    pub fn new() -> DrawList<'a> {
+        DrawList { items: vec![] }
+    }
+
+    fn push(&mut self, layer: Layer, z: i32, command: DrawCommand<'a>) {
+        self.items.push(DrawItem { layer, z, command });
+    }
+
+    /// Render `renderable` to the region `dest`.
+    pub fn renderable(&mut self, layer: Layer, z: i32, renderable: &'a dyn Renderable, dest: Rectangle) {
+        self.push(layer, z, DrawCommand::Renderable(renderable, dest, RenderOptions::default()));
+    }
+
+    /// Render `renderable` to the region `dest`, transformed by `options`.
+    pub fn renderable_ex(&mut self, layer: Layer, z: i32, renderable: &'a dyn Renderable, dest: Rectangle, options: RenderOptions) {
+        self.push(layer, z, DrawCommand::Renderable(renderable, dest, options));
+    }
+
+    /// Fill the region `rect` with `color`. Consecutive fills of the same
+    /// color are rendered in a single batch.
+    pub fn fill(&mut self, layer: Layer, z: i32, rect: Rectangle, color: Color) {
+        self.push(layer, z, DrawCommand::Fill(rect, color));
+    }
+
+    /// Draw the border of the region `rect` in `color`. Like fills, outlines
+    /// are batched.
+    pub fn outline(&mut self, layer: Layer, z: i32, rect: Rectangle, color: Color) {
+        self.push(layer, z, DrawCommand::Outline(rect, color));
+    }
+
+    /// Draw the outline of `shape` in `color`.
+    pub fn shape(&mut self, layer: Layer, z: i32, shape: &Shape, color: Color) {
+        let mut points: Vec<SdlPoint> = shape.outline().into_iter()
+            .map(|point| SdlPoint::new(point.x as i32, point.y as i32))
+            .collect();
+        if let Some(&first) = points.first() {
+            points.push(first);
+        }
+        self.push(layer, z, DrawCommand::Lines(points, color));
+    }
+
+    /// Render anything, using the renderer directly.
+    pub fn custom<F>(&mut self, layer: Layer, z: i32, render: F)
+    where
+        F: FnOnce(&mut WindowCanvas) + 'a
+    {
+        self.push(layer, z, DrawCommand::Custom(Box::new(render)));
+    }
+
+    /// Render every item, from the back to the front, then empty the list.
+    pub fn flush(&mut self, renderer: &mut WindowCanvas, camera: &Camera) {
+        // The sort is stable, which keeps the submission order within a `z`.
+        self.items.sort_by_key(|item| (item.layer, item.z));
+
+        // The rectangles waiting to be rendered, all filled or outlined with
+        // the same color.
+        let mut batch: Vec<SdlRect> = vec![];
+        let mut batch_kind = None;
+        let mut camera_applied = None;
+
+        for item in self.items.drain(..) {
+            let follows_camera = item.layer.follows_camera();
+            let kind = match item.command {
+                DrawCommand::Fill(_, color) => Some((true, color)),
+                DrawCommand::Outline(_, color) => Some((false, color)),
+                _ => None,
+            };
+
+            // Render the pending batch once we reach something else.
+            if !batch.is_empty() && (kind != batch_kind || camera_applied != Some(follows_camera)) {
+                render_batch(renderer, &batch, batch_kind.unwrap());
+                batch.clear();
+            }
+
+            if camera_applied != Some(follows_camera) {
+                if follows_camera {
+                    camera.apply(renderer);
+                } else {
+                    Camera::reset(renderer);
+                }
+                camera_applied = Some(follows_camera);
+            }
+
+            match item.command {
+                DrawCommand::Renderable(renderable, dest, options) =>
+                    renderable.render_ex(renderer, dest, &options),
+
+                DrawCommand::Fill(rect, _) | DrawCommand::Outline(rect, _) => {
+                    batch_kind = kind;
+                    batch.push(rect.to_sdl());
+                },
+
+                DrawCommand::Lines(points, color) => {
+                    renderer.set_draw_color(color);
+                    renderer.draw_lines(&points[..]).unwrap();
+                },
+
+                DrawCommand::Custom(render) =>
+                    render(renderer),
+            }
+        }
+
+        if !batch.is_empty() {
+            render_batch(renderer, &batch, batch_kind.unwrap());
+        }
+    }
+}
+
+fn render_batch(renderer: &mut WindowCanvas, rects: &[SdlRect], (filled, color): (bool, Color)) {
+    renderer.set_draw_color(color);
+    if filled {
+        renderer.fill_rects(rects).unwrap();
+    } else {
+        renderer.draw_rects(rects).unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+//! A minimal entity-component store, in which game objects are ids to which
+//! components are attached, and updated by systems working on every entity
+//! which has the components they need.
+
+use crate::phi::data::{Rectangle, Shape, SpatialHash, Vec2};
+use crate::phi::gfx::Animator;
+use crate::phi::pool::{Handle, HandleMap, Pool};
+
+/// A handle to an entity of a `World`, which stays valid until the entity is
+/// despawned.
+pub type Entity = Handle;
+
+/// A component of the entities which have one.
+pub type Components<T> = HandleMap<T>;
+
+/// Where an entity is on the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub rect: Rectangle,
+
+    /// The rotation, in degrees, clockwise, around the center of `rect`.
+    pub angle: f64,
+}
+
+/// How fast an entity moves and spins.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Velocity {
+    /// In pixels per second.
+    pub vel: Vec2,
+
+    /// In degrees per second, clockwise.
+    pub spin: f64,
+}
+
+/// The region in which an entity collides with others.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    /// The shape of the region, relative to the top-left corner of the
+    /// entity's `Transform`, which is ignored by collisions.
+    pub shape: Shape,
+
+    /// Whether the entity collides with everything it went through since the
+    /// last frame, so that it cannot fly through thin obstacles when it is
+    /// fast. The bounding box of the shape is swept.
+    pub swept: bool,
+}
+
+impl Collider {
+    /// The region covered by the collider during the last frame, where the
+    /// entity moved at `velocity` for `dt` seconds.
+    fn region(&self, transform: &Transform, velocity: Option<&Velocity>, dt: f64) -> Shape {
+        let shape = self.shape.translate(Vec2::new(transform.rect.x, transform.rect.y));
+        match velocity {
+            Some(velocity) if self.swept => {
+                let to = shape.bounding_box();
+                let from = to.translate(-velocity.vel * dt);
+
+                // Moving along an axis, e.g. like most bullets, it covers no
+                // more than where it started and where it ended, which is
+                // cheaper to test than a polygon.
+                if velocity.vel.x == 0.0 || velocity.vel.y == 0.0 {
+                    Shape::Rect(from.union(to))
+                } else {
+                    Shape::Polygon(from.sweep(to))
+                }
+            },
+            _ => shape,
+        }
+    }
+}
+
+/// The time left, in seconds, before the entity is despawned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lifetime {
+    pub remaining: f64,
+}
+
+/// The damage which an entity may take before it is killed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub points: u32,
+}
+
+/// How an entity left the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fate {
+    /// Its health was depleted.
+    Killed,
+
+    /// Its lifetime, or its animation, is over.
+    Expired,
+
+    /// It left the bounds of the world for good.
+    Left,
+}
+
+/// The entities of a game and their components. Every entity has a kind, of a
+/// type chosen by the game, which tells what it represents.
+pub struct World<K> {
+    /// The entities, whose slots are reused once they are despawned, so that
+    /// spawning does not allocate once the world is warmed up.
+    pub kinds: Pool<K>,
+    pub transforms: Components<Transform>,
+    pub velocities: Components<Velocity>,
+    pub animators: Components<Animator>,
+    pub colliders: Components<Collider>,
+    pub lifetimes: Components<Lifetime>,
+    pub healths: Components<Health>,
+}
+
+impl<K> Default for World<K> {
+    fn default() -> World<K> {
+        World::new()
+    }
+}
+
+impl<K> World<K> {
+    pub fn new() -> World<K> {
+        World {
+            kinds: Pool::new(),
+            transforms: Components::new(),
+            velocities: Components::new(),
+            animators: Components::new(),
+            colliders: Components::new(),
+            lifetimes: Components::new(),
+            healths: Components::new(),
+        }
+    }
+
+    /// Creates an entity of the given kind, with no other component.
+    pub fn spawn(&mut self, kind: K) -> Entity {
+        self.kinds.insert(kind)
+    }
+
+    /// Removes an entity and its components. Returns its kind, or `None` if
+    /// it was already despawned.
+    pub fn despawn(&mut self, entity: Entity) -> Option<K> {
+        let kind = self.kinds.remove(entity)?;
+        self.transforms.remove(entity);
+        self.velocities.remove(entity);
+        self.animators.remove(entity);
+        self.colliders.remove(entity);
+        self.lifetimes.remove(entity);
+        self.healths.remove(entity);
+        Some(kind)
+    }
+
+    /// The number of entities alive.
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    /// Moves and spins the entities by their velocity for `dt` seconds.
+    pub fn movement(&mut self, dt: f64) {
+        for (entity, velocity) in self.velocities.iter() {
+            if let Some(transform) = self.transforms.get_mut(entity) {
+                transform.rect = transform.rect.translate(velocity.vel * dt);
+                transform.angle = (transform.angle + velocity.spin * dt) % 360.0;
+            }
+        }
+    }
+
+    /// Plays the animations of the entities for `dt` seconds.
+    pub fn animation(&mut self, dt: f64) {
+        for (_, animator) in self.animators.iter_mut() {
+            animator.add_time(dt);
+        }
+    }
+
+    /// The pairs of entities whose colliders overlap, among those for which
+    /// `can_collide` holds, given that they moved for `dt` seconds since the
+    /// last frame. Entities are sorted in a grid whose cells are `cell_size`
+    /// pixels wide, which should be about the size of the largest of them.
+    pub fn collisions<F>(&self, dt: f64, cell_size: f64, can_collide: F) -> Vec<(Entity, Entity)>
+        where F: Fn(&K, &K) -> bool
+    {
+        let regions: Vec<(Entity, Shape)> = self.colliders.iter()
+            .filter_map(|(entity, collider)| {
+                let transform = self.transforms.get(entity)?;
+                Some((entity, collider.region(transform, self.velocities.get(entity), dt)))
+            })
+            .collect();
+
+        let mut grid = SpatialHash::new(cell_size);
+        for (i, (_, region)) in regions.iter().enumerate() {
+            grid.insert(region.bounding_box(), i);
+        }
+
+        let mut pairs = vec![];
+        for (i, (a, region_a)) in regions.iter().enumerate() {
+            // Every pair is found from both sides, and kept from the first.
+            for j in grid.query(region_a.bounding_box()) {
+                let (b, ref region_b) = regions[j];
+                if j > i && can_collide(self.kinds.get(*a).unwrap(), self.kinds.get(b).unwrap()) &&
+                   region_a.overlaps(region_b) {
+                    pairs.push((*a, b));
+                }
+            }
+        }
+
+        pairs
+    }
+
+    /// Ages the entities by `dt` seconds, then removes those whose lifetime or
+    /// animation is over, whose health is depleted, or which are outside of
+    /// `bounds` and not heading back into it, e.g. because they stopped.
+    /// Returns their kind, where they were and why they left, e.g. to make the
+    /// killed ones explode.
+    pub fn despawn_dead(&mut self, dt: f64, bounds: Rectangle) -> Vec<(K, Transform, Fate)> {
+        for (_, lifetime) in self.lifetimes.iter_mut() {
+            lifetime.remaining -= dt;
+        }
+
+        let fates: Vec<(Entity, Fate)> = self.kinds.iter()
+            .filter_map(|(entity, _)| {
+                if matches!(self.healths.get(entity), Some(health) if health.points == 0) {
+                    return Some((entity, Fate::Killed));
+                }
+
+                if matches!(self.lifetimes.get(entity), Some(lifetime) if lifetime.remaining <= 0.0) ||
+                   matches!(self.animators.get(entity), Some(animator) if animator.is_finished()) {
+                    return Some((entity, Fate::Expired));
+                }
+
+                let transform = self.transforms.get(entity)?;
+                let vel = self.velocities.get(entity).map_or(Vec2::default(), |velocity| velocity.vel);
+                let away = transform.rect.center() - bounds.center();
+                if !transform.rect.overlaps(bounds) && vel.dot(away) >= 0.0 {
+                    return Some((entity, Fate::Left));
+                }
+
+                None
+            })
+            .collect();
+
+        fates.into_iter()
+            .filter_map(|(entity, fate)| {
+                let transform = *self.transforms.get(entity)?;
+                self.despawn(entity).map(|kind| (kind, transform, fate))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Rectangle = Rectangle { x: 0.0, y: 0.0, w: 800.0, h: 600.0 };
+
+    /// Adds an entity of the given `kind` to the `world`, a `size` pixels wide
+    /// square colliding in full, with its top-left corner at (`x`, `y`).
+    fn square(world: &mut World<char>, kind: char, x: f64, y: f64, size: f64) -> Entity {
+        let entity = world.spawn(kind);
+        world.transforms.insert(entity, Transform { rect: Rectangle { x, y, w: size, h: size }, angle: 0.0 });
+        world.colliders.insert(entity, Collider {
+            shape: Shape::Rect(Rectangle::with_size(size, size)),
+            swept: false,
+        });
+        entity
+    }
+
+    /// The kinds of the pairs of entities which collide, sorted, so that the
+    /// order in which they are found does not matter.
+    fn colliding(world: &World<char>, dt: f64, can_collide: fn(&char, &char) -> bool) -> Vec<(char, char)> {
+        let mut pairs: Vec<(char, char)> = world.collisions(dt, 50.0, can_collide).into_iter()
+            .map(|(a, b)| (*world.kinds.get(a).unwrap(), *world.kinds.get(b).unwrap()))
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn movement_moves_and_spins() {
+        let mut world = World::new();
+        let entity = square(&mut world, 'a', 10.0, 20.0, 5.0);
+        world.velocities.insert(entity, Velocity { vel: Vec2::new(100.0, -50.0), spin: 90.0 });
+        world.movement(0.5);
+
+        let transform = world.transforms.get(entity).unwrap();
+        assert_eq!(transform.rect, Rectangle { x: 60.0, y: -5.0, w: 5.0, h: 5.0 });
+        assert_eq!(transform.angle, 45.0);
+    }
+
+    #[test]
+    fn every_overlapping_pair_is_reported_once() {
+        let mut world = World::new();
+        // Spread over several cells, and straddling their edges.
+        square(&mut world, 'a', 90.0, 90.0, 30.0);
+        square(&mut world, 'b', 110.0, 110.0, 30.0);
+        square(&mut world, 'c', 95.0, 115.0, 20.0);
+        square(&mut world, 'd', 300.0, 300.0, 30.0);
+
+        assert_eq!(colliding(&world, 0.0, |_, _| true), vec![('a', 'b'), ('a', 'c'), ('b', 'c')]);
+    }
+
+    #[test]
+    fn can_collide_filters_pairs() {
+        let mut world = World::new();
+        square(&mut world, 'a', 0.0, 0.0, 10.0);
+        square(&mut world, 'b', 5.0, 5.0, 10.0);
+        square(&mut world, 'c', 5.0, 0.0, 10.0);
+
+        assert_eq!(colliding(&world, 0.0, |x, y| *x != 'b' && *y != 'b'), vec![('a', 'c')]);
+    }
+
+    #[test]
+    fn swept_colliders_catch_what_they_flew_through() {
+        let mut world = World::new();
+        square(&mut world, 'w', 100.0, 0.0, 10.0);
+
+        // During the last 0.1 second, the bullet went from x = 50 to x = 150.
+        let bullet = square(&mut world, 'b', 150.0, 0.0, 4.0);
+        world.velocities.insert(bullet, Velocity { vel: Vec2::new(1000.0, 0.0), spin: 0.0 });
+        assert!(colliding(&world, 0.1, |_, _| true).is_empty());
+
+        world.colliders.get_mut(bullet).unwrap().swept = true;
+        assert_eq!(colliding(&world, 0.1, |_, _| true), vec![('b', 'w')]);
+
+        // Along a diagonal too.
+        world.velocities.get_mut(bullet).unwrap().vel = Vec2::new(1000.0, 1000.0);
+        world.transforms.get_mut(bullet).unwrap().rect = Rectangle { x: 150.0, y: 50.0, w: 4.0, h: 4.0 };
+        assert_eq!(colliding(&world, 0.1, |_, _| true), vec![('b', 'w')]);
+    }
+
+    #[test]
+    fn killed_entities_are_despawned_first() {
+        let mut world = World::new();
+        let entity = square(&mut world, 'k', 10.0, 10.0, 10.0);
+        world.healths.insert(entity, Health { points: 0 });
+        world.lifetimes.insert(entity, Lifetime { remaining: 0.0 });
+        let alive = square(&mut world, 'a', 10.0, 10.0, 10.0);
+        world.healths.insert(alive, Health { points: 1 });
+
+        let dead = world.despawn_dead(0.1, BOUNDS);
+        assert_eq!(dead.len(), 1);
+        assert_eq!((dead[0].0, dead[0].2), ('k', Fate::Killed));
+        assert_eq!(dead[0].1.rect, Rectangle { x: 10.0, y: 10.0, w: 10.0, h: 10.0 });
+        assert_eq!(world.transforms.get(entity), None);
+        assert_eq!(world.len(), 1);
+    }
+
+    #[test]
+    fn entities_expire_at_the_end_of_their_lifetime() {
+        let mut world = World::new();
+        let entity = square(&mut world, 'e', 10.0, 10.0, 10.0);
+        world.lifetimes.insert(entity, Lifetime { remaining: 0.25 });
+
+        assert!(world.despawn_dead(0.125, BOUNDS).is_empty());
+        assert_eq!(world.lifetimes.get(entity), Some(&Lifetime { remaining: 0.125 }));
+
+        let dead = world.despawn_dead(0.125, BOUNDS);
+        assert_eq!(dead.iter().map(|(kind, _, fate)| (*kind, *fate)).collect::<Vec<_>>(), vec![('e', Fate::Expired)]);
+        assert!(world.is_empty());
+    }
+
+    #[test]
+    fn entities_leave_once_outside_and_not_coming_back() {
+        let mut world = World::new();
+        let coming = square(&mut world, 'c', 800.0, 100.0, 10.0);
+        world.velocities.insert(coming, Velocity { vel: Vec2::new(-100.0, 0.0), spin: 0.0 });
+        let inside = square(&mut world, 'i', 400.0, 300.0, 10.0);
+        world.velocities.insert(inside, Velocity::default());
+        let going = square(&mut world, 'g', -20.0, 100.0, 10.0);
+        world.velocities.insert(going, Velocity { vel: Vec2::new(-100.0, 0.0), spin: 0.0 });
+
+        // Stopped outside, or without a velocity at all, they would stay
+        // forever, and a stage would never be cleared.
+        let stopped = square(&mut world, 's', 900.0, 100.0, 10.0);
+        world.velocities.insert(stopped, Velocity::default());
+        square(&mut world, 'n', 100.0, 700.0, 10.0);
+
+        let mut left: Vec<(char, Fate)> = world.despawn_dead(0.1, BOUNDS).into_iter()
+            .map(|(kind, _, fate)| (kind, fate))
+            .collect();
+        left.sort_by_key(|&(kind, _)| kind);
+        assert_eq!(left, vec![('g', Fate::Left), ('n', Fate::Left), ('s', Fate::Left)]);
+        assert!(world.kinds.get(coming).is_some());
+        assert!(world.kinds.get(inside).is_some());
+    }
+}
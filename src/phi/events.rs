@@ -16,7 +16,10 @@ macro_rules! struct_events {
             // None         => Nothing happening _now_
             $( pub $k_alias: Option<bool>, )*
             $( pub $e_alias: bool, )*
-            resize: Option<(u32, u32)>
+            resize: Option<(u32, u32)>,
+
+            // The text typed since the last frame
+            pub text: String
         }
 
         impl ImmediateEvents {
@@ -26,7 +29,8 @@ macro_rules! struct_events {
                     // set to None
                     $( $k_alias: None, )*
                     $( $e_alias: false, )*
-                    resize: None
+                    resize: None,
+                    text: String::new()
                 }
             }
         }
@@ -96,6 +100,10 @@ macro_rules! struct_events {
                             _ => {}
                         },
 
+                        TextInput { text, .. } => {
+                            self.now.text.push_str(&text);
+                        },
+
                         $(
                             $e_sdl => {
                                 self.now.$e_alias = true;
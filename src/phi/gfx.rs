@@ -1,21 +1,89 @@
-use crate::phi::data::Rectangle;
-use crate::phi::Phi;
+use crate::phi::data::{Rectangle, Mask, MaskPlacement, Vec2};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
 use std::rc::Rc;
-use sdl2::render::{WindowCanvas, Texture};
-use sdl2::image::LoadTexture;
+use sdl2::render::{WindowCanvas, Texture, BlendMode};
+use sdl2::image::LoadSurface;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Point;
+use sdl2::surface::Surface;
+use serde::{Serialize, Deserialize};
 
 /// Common interface for rendering a graphical component to some given region
 /// of the window.
 pub trait Renderable {
-    fn render(&self, renderer: &mut WindowCanvas, dest: Rectangle);
+    fn render(&self, renderer: &mut WindowCanvas, dest: Rectangle) {
+        self.render_ex(renderer, dest, &RenderOptions::default());
+    }
+
+    /// Renders the component while applying the transformations described by
+    /// `options`, e.g. rotation, flipping or color modulation.
+    fn render_ex(&self, renderer: &mut WindowCanvas, dest: Rectangle, options: &RenderOptions);
+}
+
+/// Describes how a sprite should be transformed when it is copied to the
+/// screen. The default value renders the sprite as-is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// The rotation applied to the sprite, in degrees, clockwise.
+    pub angle: f64,
+
+    /// The point around which the sprite rotates, relative to the top-left
+    /// corner of the destination. If `None`, the sprite rotates around the
+    /// center of the destination.
+    pub pivot: Option<(f64, f64)>,
+
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+
+    /// The opacity of the sprite, from 0 (invisible) to 255 (opaque).
+    pub alpha: u8,
+
+    /// The color by which every pixel of the sprite is multiplied. White
+    /// leaves the sprite unchanged.
+    pub tint: Color,
+
+    /// How the sprite is blended with what was previously rendered. If `None`,
+    /// the texture's own blend mode is kept.
+    pub blend: Option<BlendMode>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            angle: 0.0,
+            pivot: None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            alpha: 255,
+            tint: Color::RGB(255, 255, 255),
+            blend: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Whether rendering with these options requires more than a plain copy.
+    fn is_transformed(&self) -> bool {
+        self.angle != 0.0 || self.flip_horizontal || self.flip_vertical
+    }
+
+    /// Whether rendering with these options changes the texture's modulation.
+    fn is_modulated(&self) -> bool {
+        self.alpha != 255 || self.blend.is_some() ||
+        (self.tint.r, self.tint.g, self.tint.b) != (255, 255, 255)
+    }
 }
 
 #[derive(Clone)]
 pub struct Sprite {
     tex: Rc<RefCell<Texture>>,
     src: Rectangle,
+
+    /// The opaque pixels of the whole texture, if it was loaded from a file.
+    mask: Option<Rc<Mask>>,
 }
 
 impl Sprite {
@@ -29,14 +97,28 @@ impl Sprite {
                 h: tex_query.height as f64,
                 x: 0.0,
                 y: 0.0,
-            }
+            },
+            mask: None,
         }
     }
 
     /// Creates a new sprite form an image file located at the given path,
     /// Returns `Some` if the file could be read, and `None` otherwise.
+    ///
+    /// The collision mask of the image is computed along the way, so that the
+    /// sprite and its regions support pixel-perfect collisions.
     pub fn load(renderer: &WindowCanvas, path: &str) -> Option<Sprite> {
-        renderer.texture_creator().load_texture(Path::new(path)).ok().map(Sprite::new)
+        let surface = Surface::from_file(Path::new(path)).ok()?
+            .convert_format(PixelFormatEnum::RGBA32).ok()?;
+        let (w, h) = surface.size();
+        let pitch = surface.pitch() as usize;
+        let mask = surface.with_lock(|pixels| Mask::from_rgba(w as usize, h as usize, pitch, pixels));
+
+        let texture = renderer.texture_creator().create_texture_from_surface(&surface).ok()?;
+        Some(Sprite {
+            mask: Some(Rc::new(mask)),
+            ..Sprite::new(texture)
+        })
     }
 
     /// Returns a new `Sprite` representing a sub-region of the current one.
@@ -44,17 +126,14 @@ impl Sprite {
     /// Returns `Some` if the `rect` is valid, i.e. included in the current
     /// region, and `None` otherwise.
     pub fn region(&self, rect: Rectangle) -> Option<Sprite> {
-        let new_src = Rectangle {
-            x: rect.x + self.src.x,
-            y: rect.y + self.src.y,
-            ..rect
-        };
+        let new_src = rect.translate(Vec2::new(self.src.x, self.src.y));
 
         // Verify that the region requested region is inside of the current one
         if self.src.contains(new_src) {
             Some(Sprite {
                 tex: self.tex.clone(),
                 src: new_src,
+                mask: self.mask.clone(),
             })
         } else {
             None
@@ -65,14 +144,99 @@ impl Sprite {
     pub fn size(&self) -> (f64, f64) {
         (self.src.w, self.src.h)
     }
+
+    /// The collision mask of the sprite, if it has one, and where the sprite
+    /// ends up once rendered to `dest` with the rotation and flips of
+    /// `options`. Sprites are assumed to rotate around their center.
+    pub fn mask(&self, dest: Rectangle, options: &RenderOptions) -> Option<(&Mask, MaskPlacement)> {
+        self.mask.as_ref().map(|mask| (&**mask, MaskPlacement {
+            region: self.src,
+            dest,
+            angle: options.angle,
+            flip_horizontal: options.flip_horizontal,
+            flip_vertical: options.flip_vertical,
+        }))
+    }
+
+    /// Whether the opaque pixels of the sprite, rendered to `dest` with
+    /// `options`, cover those of `other`, rendered to `other_dest` with
+    /// `other_options`. Sprites without a mask are considered opaque.
+    ///
+    /// This is expensive, so it should only be called once the cheap
+    /// `Rectangle::overlaps` says that the sprites overlap.
+    pub fn overlaps(&self, dest: Rectangle, options: &RenderOptions, other: &Sprite, other_dest: Rectangle, other_options: &RenderOptions) -> bool {
+        match (self.mask(dest, options), other.mask(other_dest, other_options)) {
+            (Some((mask, placement)), Some((other_mask, other_placement))) =>
+                mask.overlaps(placement, other_mask, other_placement),
+            _ => true,
+        }
+    }
+
+    /// Frees the texture of the sprite, unless other sprites still use it, in
+    /// which case the sprite is given back so that this can be tried again
+    /// later. Textures are otherwise only freed when the renderer is dropped.
+    pub fn destroy(self) -> Result<(), Sprite> {
+        match Rc::try_unwrap(self.tex) {
+            //? This is safe as long as the renderer which created the texture
+            //? is alive, which is always the case while the game is running.
+            Ok(tex) => {
+                unsafe { tex.into_inner().destroy(); }
+                Ok(())
+            },
+            Err(tex) => Err(Sprite { tex, ..self }),
+        }
+    }
 }
 
 impl Renderable for Sprite {
-    fn render(&self, renderer: &mut WindowCanvas, dest: Rectangle) {
-        renderer.copy(&mut self.tex.borrow_mut(), self.src.to_sdl(), dest.to_sdl()).unwrap();
+    fn render_ex(&self, renderer: &mut WindowCanvas, dest: Rectangle, options: &RenderOptions) {
+        let mut tex = self.tex.borrow_mut();
+
+        if !options.is_transformed() && !options.is_modulated() {
+            renderer.copy(&tex, self.src.to_sdl(), dest.to_sdl()).unwrap();
+            return;
+        }
+
+        //? The texture is shared by every sprite cut out of the same image, so
+        //? we remember its modulation and restore it once we are done.
+        let old_color = tex.color_mod();
+        let old_alpha = tex.alpha_mod();
+        let old_blend = tex.blend_mode();
+
+        tex.set_color_mod(options.tint.r, options.tint.g, options.tint.b);
+        tex.set_alpha_mod(options.alpha);
+        if let Some(blend) = options.blend {
+            tex.set_blend_mode(blend);
+        }
+
+        renderer.copy_ex(
+            &tex,
+            self.src.to_sdl(),
+            dest.to_sdl(),
+            options.angle,
+            options.pivot.map(|(x, y)| Point::new(x as i32, y as i32)),
+            options.flip_horizontal,
+            options.flip_vertical,
+        ).unwrap();
+
+        tex.set_color_mod(old_color.0, old_color.1, old_color.2);
+        tex.set_alpha_mod(old_alpha);
+        tex.set_blend_mode(old_blend);
     }
 }
 
+/// How an animated sprite goes through its frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    /// Go back to the first frame after the last one, forever.
+    #[default]
+    Loop,
+    /// Stop on the last frame, after which the animation is finished.
+    Once,
+    /// Go back and forth between the first and last frames, forever.
+    PingPong,
+}
+
 #[derive(Clone)]
 pub struct AnimatedSprite {
     /// The frames that will be rendered, in order.
@@ -84,15 +248,9 @@ pub struct AnimatedSprite {
     /// The total time that the sprite has been alive, from which the current frame
     /// frame is derived.
     current_time: f64,
-}
 
-pub struct AnimatedSpriteDescr<'a> {
-    pub image_path: &'a str,
-    pub total_frames: usize,
-    pub frames_high: usize,
-    pub frames_wide: usize,
-    pub frame_w: f64,
-    pub frame_h: f64,
+    /// What happens once the last frame is reached.
+    mode: PlaybackMode,
 }
 
 impl AnimatedSprite {
@@ -102,6 +260,7 @@ impl AnimatedSprite {
             sprites: Rc::new(sprites),
             frame_delay: frame_delay,
             current_time: 0.0,
+            mode: PlaybackMode::Loop,
         }
     }
 
@@ -123,6 +282,68 @@ impl AnimatedSprite {
         self.sprites.len()
     }
 
+    /// Returns the dimensions of the first frame of the animation.
+    pub fn size(&self) -> (f64, f64) {
+        self.sprites[0].size()
+    }
+
+    /// The time it takes to go through every frame once, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 * self.frame_delay.abs()
+    }
+
+    /// Set what happens once the last frame is reached.
+    pub fn set_mode(&mut self, mode: PlaybackMode) {
+        self.mode = mode;
+    }
+
+    /// Go back to the first frame of the animation.
+    pub fn restart(&mut self) {
+        self.current_time = 0.0;
+    }
+
+    /// Whether the animation was played `Once` and reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.mode == PlaybackMode::Once && self.current_time >= self.duration()
+    }
+
+    /// How far the animation has progressed through its frames, from 0 to 1.
+    /// Looping animations start over at 0 every time they wrap around.
+    pub fn progress(&self) -> f64 {
+        match self.mode {
+            PlaybackMode::Once => (self.current_time / self.duration()).min(1.0),
+            _ => (self.current_time % self.duration()) / self.duration(),
+        }
+    }
+
+    /// The frame which should be rendered now, e.g. to test collisions with
+    /// its mask.
+    pub fn sprite(&self) -> &Sprite {
+        &self.sprites[self.current_frame()]
+    }
+
+    /// The index of the frame which should be rendered now.
+    fn current_frame(&self) -> usize {
+        let frames = self.frames();
+        let step = (self.current_time / self.frame_delay) as usize;
+
+        match self.mode {
+            PlaybackMode::Loop => step % frames,
+            PlaybackMode::Once => step.min(frames - 1),
+            PlaybackMode::PingPong => {
+                if frames < 2 {
+                    return 0;
+                }
+
+                // Going from the first frame to the last one and back takes
+                // `2 * frames - 2` steps, because both ends are shown once.
+                let period = 2 * frames - 2;
+                let step = step % period;
+                if step < frames { step } else { period - step }
+            },
+        }
+    }
+
     /// Set the time it takes to get from one frame to the next, in seconds.
     /// If the value is negatice, then we "rewind" the animation.
     pub fn set_frame_delay(&mut self, frame_delay: f64) {
@@ -150,51 +371,306 @@ impl AnimatedSprite {
             self.current_time = (self.frames() - 1) as f64 * self.frame_delay;
         }
     }
+}
 
-    pub fn load_frames(phi: &mut Phi, descr: AnimatedSpriteDescr) -> Vec<Sprite> {
-        // Read the asteroid's iamge from the filesystem and construct an 
-        // animated sprite out of it.
+impl Renderable for AnimatedSprite {
+    /// Renders the current frame of the sprite.
+    fn render_ex(&self, renderer: &mut WindowCanvas, dest: Rectangle, options: &RenderOptions) {
+        self.sprite().render_ex(renderer, dest, options);
+    }
+}
 
-        let spritesheet = Sprite::load(&mut phi.renderer, descr.image_path).unwrap();
-        let mut frames = Vec::with_capacity(descr.total_frames);
+/// A state machine choosing which of several named animations, or _clips_, an
+/// entity currently plays, e.g. "idle", "bank_up" or "explode".
+#[derive(Clone)]
+pub struct Animator {
+    clips: HashMap<String, AnimatedSprite>,
 
-        for yth in 0..descr.frames_high {
-            for xth in 0.. descr.frames_wide {
-                if descr.frames_wide * yth + xth >= descr.total_frames {
-                    break;
-                }
+    /// When the clip named by the key is finished, the animator automatically
+    /// starts playing the clip named by the value.
+    transitions: HashMap<String, String>,
+
+    current: String,
+}
+
+impl Animator {
+    /// Creates an animator playing `clip`, known as `name`.
+    pub fn new(name: &str, clip: AnimatedSprite) -> Animator {
+        let mut clips = HashMap::new();
+        clips.insert(name.to_string(), clip);
 
-                frames.push(
-                    spritesheet.region(Rectangle {
-                        w: descr.frame_w,
-                        h: descr.frame_h,
-                        x: descr.frame_w * xth as f64,
-                        y: descr.frame_h * yth as f64,
-                    }).unwrap());
+        Animator {
+            clips,
+            transitions: HashMap::new(),
+            current: name.to_string(),
+        }
+    }
+
+    /// Makes `clip` available under `name`, replacing any clip of that name.
+    pub fn add_clip(&mut self, name: &str, clip: AnimatedSprite) {
+        self.clips.insert(name.to_string(), clip);
+    }
+
+    /// Automatically play the clip `to` whenever the clip `from` is finished.
+    pub fn add_transition(&mut self, from: &str, to: &str) {
+        self.transitions.insert(from.to_string(), to.to_string());
+    }
+
+    /// Start playing the clip called `name` from its first frame, unless it is
+    /// already being played. Panics if there is no such clip.
+    pub fn play(&mut self, name: &str) {
+        if self.current != name {
+            self.switch_to(name);
+        }
+    }
+
+    /// Start playing the clip called `name` from its first frame.
+    fn switch_to(&mut self, name: &str) {
+        self.clips.get_mut(name)
+            .unwrap_or_else(|| panic!("Animator has no clip called `{}`", name))
+            .restart();
+        self.current = name.to_string();
+    }
+
+    /// The clip being played.
+    pub fn clip(&self) -> &AnimatedSprite {
+        &self.clips[&self.current]
+    }
+
+    /// Whether the current clip is finished, and no transition follows it.
+    pub fn is_finished(&self) -> bool {
+        self.clip().is_finished() && !self.transitions.contains_key(&self.current)
+    }
+
+    /// Advances the current clip by `dt` seconds, following the transitions of
+    /// the clips which finish.
+    pub fn add_time(&mut self, dt: f64) {
+        let clip = self.clips.get_mut(&self.current).unwrap();
+        clip.add_time(dt);
+
+        if clip.is_finished() {
+            if let Some(next) = self.transitions.get(&self.current).cloned() {
+                self.switch_to(&next);
             }
         }
+    }
 
-        frames
+    /// Returns the dimensions of the current clip.
+    pub fn size(&self) -> (f64, f64) {
+        self.clip().size()
     }
 }
 
-impl Renderable for AnimatedSprite {
-    /// Renders the current frame of the sprite.
-    fn render(&self, renderer: &mut WindowCanvas, dest: Rectangle) {
-        let current_frame = 
-            (self.current_time / self.frame_delay) as usize % self.frames();
-
-        let sprite = &self.sprites[current_frame];
-        sprite.render(renderer, dest);
+impl Renderable for Animator {
+    /// Renders the current frame of the current clip.
+    fn render_ex(&self, renderer: &mut WindowCanvas, dest: Rectangle, options: &RenderOptions) {
+        self.clip().render_ex(renderer, dest, options);
     }
 }
 
 pub trait CopySprite<T> {
     fn copy_sprite(&mut self, sprite: &T, dest: Rectangle);
+
+    /// Same as `copy_sprite`, but applies the given `options` to the sprite.
+    fn copy_sprite_ex(&mut self, sprite: &T, dest: Rectangle, options: &RenderOptions);
 }
 
 impl<T: Renderable> CopySprite<T> for WindowCanvas {
     fn copy_sprite(&mut self, renderable: &T, dest: Rectangle) {
         renderable.render(self, dest);
     }
-}
\ No newline at end of file
+
+    fn copy_sprite_ex(&mut self, renderable: &T, dest: Rectangle, options: &RenderOptions) {
+        renderable.render_ex(self, dest, options);
+    }
+}
+
+
+/// A character of a bitmap font.
+#[derive(Clone)]
+struct Glyph {
+    /// The region of the font's page showing the character, or `None` if it
+    /// is invisible, e.g. for spaces.
+    sprite: Option<Sprite>,
+    x_offset: f64,
+    y_offset: f64,
+    x_advance: f64,
+}
+
+/// A font whose characters are pre-rendered in an image, as described by an
+/// AngelCode BMFont file in the text format. It is meant for pixel fonts, which
+/// stay sharp when they are scaled by an integer factor.
+#[derive(Clone)]
+pub struct BitmapFont {
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f64>,
+    line_height: f64,
+}
+
+impl BitmapFont {
+    /// Loads the font described by the `.fnt` file at `path`, along with its
+    /// pages. Returns an error message if either could not be read.
+    pub fn load(renderer: &WindowCanvas, path: &str) -> Result<BitmapFont, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut pages = HashMap::new();
+        let mut glyphs = HashMap::new();
+        let mut kerning = HashMap::new();
+        let mut line_height = 0.0;
+
+        for line in source.lines() {
+            let (tag, attributes) = BitmapFont::parse_line(line);
+            let number = |key: &str| -> Result<f64, String> {
+                attributes.get(key).and_then(|value| value.parse().ok())
+                    .ok_or_else(|| format!("{}: missing or invalid `{}` in `{}`", path, key, line))
+            };
+            let character = |key: &str| -> Result<char, String> {
+                ::std::char::from_u32(number(key)? as u32)
+                    .ok_or_else(|| format!("{}: invalid character in `{}`", path, line))
+            };
+
+            match tag {
+                "common" => line_height = number("lineHeight")?,
+
+                "page" => {
+                    let file = attributes.get("file")
+                        .ok_or_else(|| format!("{}: page without a file", path))?;
+                    let page = Sprite::load(renderer, directory.join(file).to_str().unwrap())
+                        .ok_or_else(|| format!("{}: could not load {}", path, file))?;
+                    pages.insert(number("id")? as usize, page);
+                },
+
+                "char" => {
+                    let page = pages.get(&(number("page")? as usize))
+                        .ok_or_else(|| format!("{}: unknown page in `{}`", path, line))?;
+                    let (w, h) = (number("width")?, number("height")?);
+
+                    let sprite =
+                        if w == 0.0 || h == 0.0 { None }
+                        else {
+                            Some(page.region(Rectangle { x: number("x")?, y: number("y")?, w, h })
+                                .ok_or_else(|| format!("{}: character outside of its page in `{}`", path, line))?)
+                        };
+
+                    glyphs.insert(character("id")?, Glyph {
+                        sprite,
+                        x_offset: number("xoffset")?,
+                        y_offset: number("yoffset")?,
+                        x_advance: number("xadvance")?,
+                    });
+                },
+
+                "kerning" => {
+                    kerning.insert((character("first")?, character("second")?), number("amount")?);
+                },
+
+                _ => {},
+            }
+        }
+
+        Ok(BitmapFont {
+            glyphs,
+            kerning,
+            line_height,
+        })
+    }
+
+    /// Splits a line of a BMFont file, such as `char id=32 x=0 ...`, into its
+    /// tag and its attributes. Values may be quoted to include spaces.
+    fn parse_line(line: &str) -> (&str, HashMap<&str, String>) {
+        let line = line.trim();
+        let (tag, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut attributes = HashMap::new();
+        let mut rest = rest.trim_start();
+
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim();
+            let after = &rest[eq + 1..];
+
+            let (value, remainder) =
+                if let Some(quoted) = after.strip_prefix('"') {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    (quoted[..end].to_string(), quoted.get(end + 1..).unwrap_or(""))
+                } else {
+                    let end = after.find(' ').unwrap_or(after.len());
+                    (after[..end].to_string(), &after[end..])
+                };
+
+            attributes.insert(key, value);
+            rest = remainder.trim_start();
+        }
+
+        (tag, attributes)
+    }
+
+    /// The glyph used to render `c`. Characters missing from the font are
+    /// rendered as `?` if possible, and skipped otherwise.
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c)
+            .or_else(|| self.glyphs.get(&c.to_ascii_uppercase()))
+            .or_else(|| self.glyphs.get(&'?'))
+    }
+
+    /// Computes the size, in pixels, of `text` rendered at the given `scale`.
+    pub fn measure(&self, text: &str, scale: u32) -> (f64, f64) {
+        let scale = scale as f64;
+        let mut w: f64 = 0.0;
+        let mut lines = 0;
+
+        for line in text.split('\n') {
+            let mut prev = None;
+            let mut line_w = 0.0;
+
+            for c in line.chars() {
+                if let Some(glyph) = self.glyph(c) {
+                    line_w += (glyph.x_advance + self.kerning_between(prev, c)) * scale;
+                }
+                prev = Some(c);
+            }
+
+            w = w.max(line_w);
+            lines += 1;
+        }
+
+        (w, lines as f64 * self.line_height * scale)
+    }
+
+    fn kerning_between(&self, prev: Option<char>, c: char) -> f64 {
+        prev.and_then(|prev| self.kerning.get(&(prev, c)).cloned()).unwrap_or(0.0)
+    }
+
+    /// Renders `text` with its top-left corner at (`x`, `y`), every pixel of
+    /// the font being drawn as a `scale`×`scale` square.
+    pub fn render(&self, renderer: &mut WindowCanvas, text: &str, x: f64, y: f64, scale: u32) {
+        let scale = scale as f64;
+        let mut pen_y = y;
+
+        for line in text.split('\n') {
+            let mut pen_x = x;
+            let mut prev = None;
+
+            for c in line.chars() {
+                if let Some(glyph) = self.glyph(c) {
+                    pen_x += self.kerning_between(prev, c) * scale;
+
+                    if let Some(ref sprite) = glyph.sprite {
+                        let (w, h) = sprite.size();
+                        renderer.copy_sprite(sprite, Rectangle {
+                            x: pen_x + glyph.x_offset * scale,
+                            y: pen_y + glyph.y_offset * scale,
+                            w: w * scale,
+                            h: h * scale,
+                        });
+                    }
+
+                    pen_x += glyph.x_advance * scale;
+                }
+                prev = Some(c);
+            }
+
+            pen_y += self.line_height * scale;
+        }
+    }
+}
@@ -4,13 +4,30 @@
 // the compilation timeline.
 #[macro_use]
 mod events;
+pub mod atlas;
+pub mod camera;
+pub mod capture;
+pub mod console;
 pub mod data;
+pub mod debug;
+pub mod draw;
+pub mod ecs;
 pub mod gfx;
+pub mod particles;
+pub mod pool;
+pub mod text;
 
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use sdl2::render::WindowCanvas;
-use self::gfx::Sprite;
-use sdl2::pixels::Color;
 use std::path::Path;
+use self::camera::Camera;
+use self::capture::FrameCapture;
+use self::console::Console;
+use self::debug::DebugOverlay;
+use self::draw::DrawList;
+use self::gfx::Sprite;
+use self::text::{TextCache, TextStyle};
 
 struct_events! {
     keyboard: {
@@ -24,7 +41,12 @@ struct_events! {
 
         key_1: Num1,
         key_2: Num2,
-        key_3: Num3
+        key_3: Num3,
+
+        key_debug: F3,
+        key_console: Backquote,
+        key_backspace: Backspace,
+        key_screenshot: F12
     },
     else: {
         quit: Quit { .. }
@@ -36,12 +58,40 @@ struct_events! {
 pub struct Phi {
     pub events: Events,
     pub renderer: WindowCanvas,
+    pub text: TextCache,
+
+    /// Transforms everything rendered by the views. It is reset after every
+    /// frame is rendered.
+    pub camera: Camera,
+
+    /// Toggled with F3.
+    pub debug: DebugOverlay,
+
+    /// Toggled with the backtick key. The view is paused while it is open.
+    pub console: Console,
+
+    /// How fast time passes for the views, 1.0 being the normal speed.
+    pub time_scale: f64,
+
+    /// The source of randomness of the game, which can be seeded from the
+    /// console to replay the same game.
+    pub rng: StdRng,
+
+    /// Saves the rendered frames, if a capture was started.
+    capture: Option<FrameCapture>,
 }
 
 impl Phi{
     fn new(events: Events, renderer: WindowCanvas) -> Phi {
         Phi {
             events: events,
+            text: TextCache::new(),
+            camera: Camera::new(),
+            debug: DebugOverlay::new(&renderer),
+            console: Console::new(&renderer),
+            time_scale: 1.0,
+            rng: StdRng::from_entropy(),
+            capture: None,
             renderer: renderer,
         }
     }
@@ -51,13 +101,27 @@ impl Phi{
         (w as f64, h as f64)
     }
 
-    pub fn ttf_str_sprite(&mut self, text: &str, font_path: &'static str, size: i32, color: Color) -> Option<Sprite> {
-        ::sdl2::ttf::init().unwrap().load_font(Path::new(font_path), size as u16).ok()
-            .and_then(|font| font
-                .render(text).blended(color).ok()
-                .and_then(|surface| self.renderer.create_texture_from_surface(&surface).ok())
-                .map(Sprite::new)
-        )
+    /// Render every item of `list`, from the back to the front.
+    pub fn draw(&mut self, mut list: DrawList) {
+        list.flush(&mut self.renderer, &self.camera);
+    }
+
+    /// Saves what was rendered so far during the current frame as a PNG image
+    /// at `path`.
+    pub fn screenshot(&self, path: &Path) -> Result<(), String> {
+        capture::save_png(&self.renderer, path)
+    }
+
+    /// From now on, save every `every`-th frame as a PNG image in `dir`.
+    pub fn start_capture(&mut self, dir: &str, every: u64) -> Result<(), String> {
+        self.capture = Some(FrameCapture::new(dir, every)?);
+        Ok(())
+    }
+
+    /// Returns a sprite showing `text` in the given style. The sprite is
+    /// cached, so this may be called every frame.
+    pub fn text_sprite(&mut self, text: &str, style: &TextStyle) -> Option<Sprite> {
+        self.text.sprite(&self.renderer, text, style)
     }
 }
 
@@ -83,18 +147,51 @@ pub trait View {
     /// disallows mutating the object by default, although you may still do it
     /// through a `RefCell` if you need to.
     fn render(&self, context: &mut Phi);
+
+    /// Called when a command typed in the console is not handled by Phi, with
+    /// the command split into words. Returns what should be printed in the
+    /// console.
+    fn command(&mut self, _context: &mut Phi, args: &[&str]) -> Result<String, String> {
+        Err(format!("unknown command `{}`", args.join(" ")))
+    }
 }
 
-pub fn spawn<F>(title: &str, init: F)
-where 
-    F: Fn(&mut Phi) -> Box<dyn View>
-{
-    // Initialize sdl2
-    let sdl_context = sdl2::init().unwrap();
-    let video = sdl_context.video().unwrap();
-    let mut timer = sdl_context.timer().unwrap();
-    let _image_context = ::sdl2::image::init(::sdl2::image::InitFlag::PNG).unwrap();
-    
+/// Runs a command typed in the console. The commands which Phi does not know
+/// are passed on to the current view.
+fn run_command(context: &mut Phi, view: &mut dyn View, line: &str) -> Result<String, String> {
+    let args: Vec<&str> = line.split_whitespace().collect();
+
+    match args[..] {
+        ["timescale", scale] => {
+            let scale: f64 = scale.parse()
+                .ok().filter(|&scale| scale >= 0.0)
+                .ok_or_else(|| format!("invalid time scale `{}`", scale))?;
+            context.time_scale = scale;
+            Ok(format!("time scale set to {}", scale))
+        },
+
+        ["seed", seed] => {
+            let seed: u64 = seed.parse().map_err(|_| format!("invalid seed `{}`", seed))?;
+            context.rng = StdRng::seed_from_u64(seed);
+            Ok(format!("seed set to {}", seed))
+        },
+
+        ["fps"] =>
+            Ok(format!("{} fps", context.debug.fps())),
+
+        _ => view.command(context, &args),
+    }
+}
+
+/// The size of the window, in pixels, when the game starts.
+const WINDOW_W: u32 = 800;
+const WINDOW_H: u32 = 600;
+
+/// The duration of a frame when views are run by `render_headless`.
+#[cfg(test)]
+const HEADLESS_FRAME: f64 = 1.0 / 60.0;
+
+fn open_audio() -> Result<(), String> {
     // Initialize audio plugin
     //? We will stick to the Ogg format throughout this article. However, you
     //? can easily require other ones.
@@ -106,15 +203,78 @@ where
     //?   * Samples are 1024 bytes in size.
     //? You don't really need to understand what all of this means. I myself just
     //? copy-pasted this from andelf's demo. ;-)
-    ::sdl2::mixer::open_audio(44100, ::sdl2::mixer::AUDIO_S16LSB, 2, 1024).unwrap();
+    ::sdl2::mixer::open_audio(44100, ::sdl2::mixer::AUDIO_S16LSB, 2, 1024)?;
     //? This function asks us how many channels we wish to allocate for our game.
     //? That is, how many sounds do we wish to be able to play at the same time?
     //? While testing, 16 channels seemed to be sufficient. Which means that we
     //? should probably request 32 of 'em just in case. :-°
     ::sdl2::mixer::allocate_channels(32);
+    Ok(())
+}
+
+/// Runs the view created by `init` for `frames` frames of a 60th of a second
+/// each, without any input, in a hidden window rendered in software. Returns
+/// the size of the last frame and its RGBA pixels.
+///
+/// The randomness is seeded with `seed`, so that the same frame is rendered
+/// every time, e.g. to compare it with a reference image.
+#[cfg(test)]
+pub fn render_headless<F>(seed: u64, frames: u32, init: F) -> Result<((u32, u32), Vec<u8>), String>
+where
+    F: FnOnce(&mut Phi) -> Box<dyn View>
+{
+    let sdl_context = sdl2::init()?;
+    let video = sdl_context.video()?;
+    let _image_context = ::sdl2::image::init(::sdl2::image::InitFlag::PNG)?;
+    open_audio()?;
+
+    let window = video.window("Phi", WINDOW_W, WINDOW_H)
+        .hidden()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut context = Phi::new(
+        Events::new(sdl_context.event_pump()?),
+        window.into_canvas()
+            .software()
+            .build().map_err(|e| e.to_string())?,
+    );
+    context.rng = StdRng::seed_from_u64(seed);
+
+    let mut current_view = init(&mut context);
+
+    for _ in 0..frames {
+        context.events.pump(&mut context.renderer);
+        context.camera.update(HEADLESS_FRAME, &mut context.rng);
+
+        current_view = match current_view.update(&mut context, HEADLESS_FRAME) {
+            ViewAction::Render(view) => view,
+            ViewAction::Quit => return Err("the view quit before the last frame".to_string()),
+        };
+    }
+
+    context.camera.apply(&mut context.renderer);
+    current_view.render(&mut context);
+    Camera::reset(&mut context.renderer);
+
+    let size = context.renderer.output_size()?;
+    let pixels = context.renderer.read_pixels(None, ::sdl2::pixels::PixelFormatEnum::RGBA32)?;
+    Ok((size, pixels))
+}
+
+pub fn spawn<F>(title: &str, init: F)
+where 
+    F: Fn(&mut Phi) -> Box<dyn View>
+{
+    // Initialize sdl2
+    let sdl_context = sdl2::init().unwrap();
+    let video = sdl_context.video().unwrap();
+    let mut timer = sdl_context.timer().unwrap();
+    let _image_context = ::sdl2::image::init(::sdl2::image::InitFlag::PNG).unwrap();
+    open_audio().unwrap();
 
     // Create the window
-    let window = video.window(title, 800, 600)
+    let window = video.window(title, WINDOW_W, WINDOW_H)
         .position_centered()
         .opengl()
         .resizable()
@@ -136,8 +296,6 @@ where
 
     let interval = 1_000 / 60;
     let mut before = timer.ticks();
-    let mut last_second = timer.ticks();
-    let mut fps = 0u16;
 
     loop {
         // Frame timing (bis)
@@ -154,23 +312,69 @@ where
         }
 
         before = now;
-        fps += 1;
-
-        if now - last_second > 1_000 {
-            println!("FPS: {}", fps);
-            last_second = now;
-            fps = 0;
-        }
-
+        context.debug.record_frame(elapsed);
 
         // Logic & rendering
 
         context.events.pump(&mut context.renderer);
+        context.camera.update(elapsed, &mut context.rng);
+
+        if context.events.now.key_debug == Some(true) {
+            context.debug.visible = !context.debug.visible;
+        }
+
+        let console_was_open = context.console.open;
+        if context.events.now.key_console == Some(true) {
+            context.console.open = !context.console.open;
+        } else if context.console.open && context.events.now.key_escape == Some(true) {
+            context.console.open = false;
+        }
 
-        match current_view.update(&mut context, elapsed) {
+        let action =
+            if context.console.open {
+                // The view stays where it is while commands are typed.
+                if let Some(line) = context.console.input(&context.events) {
+                    let output = run_command(&mut context, &mut *current_view, &line);
+                    context.console.print(output.unwrap_or_else(|e| e));
+                }
+                ViewAction::Render(current_view)
+            } else if console_was_open {
+                // The key which closed the console is not seen by the view,
+                // e.g. Escape would otherwise leave the game as well.
+                ViewAction::Render(current_view)
+            } else {
+                let elapsed = elapsed * context.time_scale;
+                current_view.update(&mut context, elapsed)
+            };
+
+        match action {
             ViewAction::Render(view) => {
                 current_view = view;
+                context.camera.apply(&mut context.renderer);
                 current_view.render(&mut context);
+                Camera::reset(&mut context.renderer);
+                if context.debug.visible {
+                    context.debug.render(&mut context.renderer);
+                }
+                if context.console.open {
+                    context.console.render(&mut context.renderer);
+                }
+
+                // Frames are read back before they are presented.
+                if context.events.now.key_screenshot == Some(true) {
+                    match capture::screenshot_path().and_then(|path| context.screenshot(&path).map(|_| path)) {
+                        Ok(path) => context.console.print(format!("Saved a screenshot to {}", path.display())),
+                        Err(e) => context.console.print(format!("Could not save a screenshot: {}", e)),
+                    }
+                }
+
+                if let Some(ref mut capture) = context.capture {
+                    if let Err(e) = capture.frame(&context.renderer) {
+                        context.console.print(format!("Stopped capturing frames: {}", e));
+                        context.capture = None;
+                    }
+                }
+
                 context.renderer.present();
             },
 
@@ -0,0 +1,171 @@
+use crate::phi::data::{Rectangle, Vec2};
+use crate::phi::gfx::{CopySprite, RenderOptions, Sprite};
+use rand::Rng;
+use sdl2::pixels::Color;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+/// Describes the particles spawned by an emitter and how they evolve.
+#[derive(Clone)]
+pub struct EmitterDescr {
+    /// The number of particles emitted every second while the emitter is
+    /// active. Bursts are emitted regardless of it.
+    pub rate: f64,
+
+    /// The range in which the lifetime of a particle is picked, in seconds.
+    pub lifetime: (f64, f64),
+
+    /// The range in which the initial speed of a particle is picked, in pixels
+    /// per second.
+    pub speed: (f64, f64),
+
+    /// The direction in which particles are emitted, in degrees, clockwise
+    /// from the right of the screen.
+    pub direction: f64,
+
+    /// How far from `direction` particles may be emitted, in degrees, on
+    /// either side.
+    pub spread: f64,
+
+    /// The acceleration applied to every particle, in pixels per second².
+    pub gravity: Vec2,
+
+    /// The color and opacity of a particle when it is born, and when it dies.
+    /// In between, they are interpolated.
+    pub start_color: Color,
+    pub end_color: Color,
+
+    /// The side of a particle when it is born, and when it dies, in pixels.
+    pub start_size: f64,
+    pub end_size: f64,
+
+    /// The image of a particle, tinted by its color. If `None`, particles are
+    /// rendered as plain squares.
+    pub sprite: Option<Sprite>,
+
+    /// The most particles that may be alive at once. No particle is emitted
+    /// while this limit is reached.
+    pub max_particles: usize,
+}
+
+struct Particle {
+    pos: Vec2,
+    vel: Vec2,
+    age: f64,
+    lifetime: f64,
+}
+
+/// Spawns, moves and renders particles, e.g. for sparks or exhaust trails.
+pub struct Emitter {
+    descr: EmitterDescr,
+    particles: Vec<Particle>,
+
+    /// Where particles are emitted when the emitter is active.
+    pub pos: Vec2,
+
+    /// Whether particles are continuously emitted at `descr.rate`.
+    pub active: bool,
+
+    /// The fraction of a particle which should have been emitted already, so
+    /// that low rates work at high framerates.
+    pending: f64,
+}
+
+fn random_between<R: Rng>(rng: &mut R, range: (f64, f64)) -> f64 {
+    range.0 + rng.gen::<f64>() * (range.1 - range.0)
+}
+
+fn lerp_u8(from: u8, to: u8, t: f64) -> u8 {
+    (from as f64 + (to as f64 - from as f64) * t) as u8
+}
+
+impl Emitter {
+    /// Creates an inactive emitter with no particles.
+    pub fn new(descr: EmitterDescr) -> Emitter {
+        Emitter {
+            particles: Vec::with_capacity(descr.max_particles),
+            descr,
+            pos: Vec2::default(),
+            active: false,
+            pending: 0.0,
+        }
+    }
+
+    /// Emits `count` particles at once from `pos`, within the particle limit.
+    pub fn burst<R: Rng>(&mut self, pos: Vec2, count: usize, rng: &mut R) {
+        for _ in 0..count {
+            if self.particles.len() >= self.descr.max_particles {
+                break;
+            }
+
+            let angle = self.descr.direction + (rng.gen::<f64>() * 2.0 - 1.0) * self.descr.spread;
+            let speed = random_between(rng, self.descr.speed);
+
+            self.particles.push(Particle {
+                pos,
+                vel: Vec2::new(speed, 0.0).rotate(angle),
+                age: 0.0,
+                lifetime: random_between(rng, self.descr.lifetime),
+            });
+        }
+    }
+
+    /// Ages and moves every particle by `dt` seconds, removes the dead ones,
+    /// then emits new particles if the emitter is active.
+    pub fn update<R: Rng>(&mut self, dt: f64, rng: &mut R) {
+        let gravity = self.descr.gravity;
+
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            particle.vel += gravity * dt;
+            particle.pos += particle.vel * dt;
+            particle.age < particle.lifetime
+        });
+
+        if self.active {
+            self.pending += self.descr.rate * dt;
+            let count = self.pending as usize;
+            self.pending -= count as f64;
+
+            let pos = self.pos;
+            self.burst(pos, count, rng);
+        } else {
+            self.pending = 0.0;
+        }
+    }
+
+    /// Renders every particle, the oldest first.
+    pub fn render(&self, renderer: &mut WindowCanvas) {
+        let old_blend = renderer.blend_mode();
+        renderer.set_blend_mode(BlendMode::Blend);
+
+        for particle in &self.particles {
+            let t = (particle.age / particle.lifetime).min(1.0);
+            let (from, to) = (self.descr.start_color, self.descr.end_color);
+            let color = Color::RGBA(
+                lerp_u8(from.r, to.r, t),
+                lerp_u8(from.g, to.g, t),
+                lerp_u8(from.b, to.b, t),
+                lerp_u8(from.a, to.a, t));
+
+            let size = self.descr.start_size + (self.descr.end_size - self.descr.start_size) * t;
+            let rect = Rectangle::with_size(size, size).center_at(particle.pos);
+
+            match self.descr.sprite {
+                Some(ref sprite) => {
+                    renderer.copy_sprite_ex(sprite, rect, &RenderOptions {
+                        tint: Color::RGB(color.r, color.g, color.b),
+                        alpha: color.a,
+                        ..Default::default()
+                    });
+                },
+
+                None => {
+                    renderer.set_draw_color(color);
+                    renderer.fill_rect(rect.to_sdl()).unwrap();
+                },
+            }
+        }
+
+        renderer.set_blend_mode(old_blend);
+    }
+}
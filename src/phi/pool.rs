@@ -0,0 +1,248 @@
+//! Containers which reuse the memory of the values removed from them, so that
+//! objects created and destroyed all the time, such as bullets, do not cost an
+//! allocation each.
+
+/// A handle to a value of a `Pool`. It stays valid, whatever is inserted or
+/// removed, until its value is removed. The slot of the value may then be
+/// reused, with another generation, so that the former handle does not refer
+/// to the newcomer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    index: usize,
+    generation: u32,
+}
+
+/// A collection of values addressed by handles, whose free slots are reused
+/// by the next insertions.
+pub struct Pool<T> {
+    /// The generation of every slot, increased whenever its value is removed,
+    /// along with the value, if any.
+    slots: Vec<(u32, Option<T>)>,
+    free: Vec<usize>,
+}
+
+impl<T> Pool<T> {
+    pub fn new() -> Pool<T> {
+        Pool {
+            slots: vec![],
+            free: vec![],
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> Handle {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push((0, None));
+                self.slots.len() - 1
+            },
+        };
+
+        let slot = &mut self.slots[index];
+        slot.1 = Some(value);
+        Handle { index, generation: slot.0 }
+    }
+
+    /// Removes the value of `handle`, if it is still there.
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        self.get(handle)?;
+
+        let slot = &mut self.slots[handle.index];
+        slot.0 += 1;
+        self.free.push(handle.index);
+        slot.1.take()
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        match self.slots.get(handle.index) {
+            Some((generation, value)) if *generation == handle.generation => value.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The number of values in the pool.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every value, along with its handle, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, (generation, value))| {
+            value.as_ref().map(|value| (Handle { index, generation: *generation }, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, (generation, value))| {
+            value.as_mut().map(|value| (Handle { index, generation: *generation }, value))
+        })
+    }
+
+    /// Updates every value in place, and removes those for which `f` returns
+    /// `false`, e.g. the bullets which left the screen.
+    pub fn retain<F: FnMut(Handle, &mut T) -> bool>(&mut self, mut f: F) {
+        for (index, (generation, value)) in self.slots.iter_mut().enumerate() {
+            let keep = match *value {
+                Some(ref mut value) => f(Handle { index, generation: *generation }, value),
+                None => continue,
+            };
+
+            if !keep {
+                *value = None;
+                *generation += 1;
+                self.free.push(index);
+            }
+        }
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Pool<T> {
+        Pool::new()
+    }
+}
+
+/// Values attached to some of the handles of a pool, e.g. a component of the
+/// entities of a world.
+pub struct HandleMap<T> {
+    slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> HandleMap<T> {
+    pub fn new() -> HandleMap<T> {
+        HandleMap { slots: vec![] }
+    }
+
+    /// Attach `value` to `handle`, replacing its former value, if any.
+    pub fn insert(&mut self, handle: Handle, value: T) {
+        if self.slots.len() <= handle.index {
+            self.slots.resize_with(handle.index + 1, || None);
+        }
+        self.slots[handle.index] = Some((handle.generation, value));
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        self.get(handle)?;
+        self.slots[handle.index].take().map(|(_, value)| value)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        match self.slots.get(handle.index) {
+            Some(Some((generation, value))) if *generation == handle.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        match self.slots.get_mut(handle.index) {
+            Some(Some((generation, value))) if *generation == handle.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Every handle which has a value, along with it.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(generation, value)| (Handle { index, generation: *generation }, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| {
+            slot.as_mut().map(|(generation, value)| (Handle { index, generation: *generation }, value))
+        })
+    }
+}
+
+impl<T> Default for HandleMap<T> {
+    fn default() -> HandleMap<T> {
+        HandleMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_values_are_gone() {
+        let mut pool = Pool::new();
+        let a = pool.insert('a');
+        let b = pool.insert('b');
+        assert_eq!(pool.len(), 2);
+
+        assert_eq!(pool.remove(a), Some('a'));
+        assert_eq!(pool.get(a), None);
+        assert_eq!(pool.remove(a), None);
+        assert_eq!(pool.get(b), Some(&'b'));
+        assert_eq!(pool.len(), 1);
+
+        assert_eq!(pool.remove(b), Some('b'));
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn retained_values_stay_and_the_others_are_gone() {
+        let mut pool = Pool::new();
+        let handles: Vec<Handle> = (0..6).map(|i| pool.insert(i)).collect();
+
+        pool.retain(|_, value| {
+            *value *= 10;
+            *value % 20 == 0
+        });
+
+        assert_eq!(pool.len(), 3);
+        for (i, &handle) in handles.iter().enumerate() {
+            let expected = if i % 2 == 0 { Some(i * 10) } else { None };
+            assert_eq!(pool.get(handle).copied(), expected);
+        }
+        assert_eq!(pool.remove(handles[1]), None);
+    }
+
+    #[test]
+    fn freed_slots_are_reused_with_a_new_generation() {
+        let mut pool = Pool::new();
+        let a = pool.insert('a');
+        pool.remove(a);
+        let b = pool.insert('b');
+
+        assert_eq!(b.index, a.index);
+        assert_ne!(b, a);
+        assert_eq!(pool.get(a), None);
+        assert_eq!(pool.get(b), Some(&'b'));
+
+        // The same goes for the slots freed by `retain`.
+        pool.retain(|_, _| false);
+        let c = pool.insert('c');
+        assert_eq!(c.index, a.index);
+        assert_eq!(pool.get(b), None);
+        assert_eq!(pool.get(c), Some(&'c'));
+        assert_eq!(pool.iter().count(), 1);
+    }
+
+    #[test]
+    fn handle_map_ignores_stale_handles() {
+        let mut pool = Pool::new();
+        let mut map = HandleMap::new();
+        let a = pool.insert(());
+        map.insert(a, "a");
+
+        pool.remove(a);
+        let b = pool.insert(());
+        assert_eq!(map.get(b), None);
+        assert_eq!(map.get_mut(b), None);
+        assert_eq!(map.remove(b), None);
+        assert_eq!(map.get(a), Some(&"a"));
+
+        // A value for the newcomer replaces that of the former handle.
+        map.insert(b, "b");
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.get(b), Some(&"b"));
+        assert_eq!(map.iter().count(), 1);
+    }
+}
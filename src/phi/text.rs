@@ -0,0 +1,271 @@
+use crate::phi::gfx::Sprite;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect as SdlRect;
+use sdl2::render::WindowCanvas;
+use sdl2::surface::Surface;
+use sdl2::ttf::{Font, Sdl2TtfContext};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The maximum number of rendered strings kept around. When it is exceeded,
+/// the string which was requested the longest time ago is dropped.
+const TEXT_CACHE_CAPACITY: usize = 256;
+
+/// How the lines of a text are aligned relative to each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Describes how a text should look.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextStyle {
+    pub font: &'static str,
+    pub size: u16,
+    pub color: Color,
+    pub align: Align,
+
+    /// If set, lines longer than this many pixels are broken between words.
+    pub wrap_width: Option<u32>,
+
+    /// The width, in pixels, and color of a border drawn around the glyphs.
+    pub outline: Option<(u16, Color)>,
+
+    /// The offset, in pixels, and color of a shadow drawn below the glyphs.
+    pub shadow: Option<((i32, i32), Color)>,
+}
+
+impl TextStyle {
+    /// A plain, left-aligned style using the given font, size and color.
+    pub fn new(font: &'static str, size: u16, color: Color) -> TextStyle {
+        TextStyle {
+            font,
+            size,
+            color,
+            align: Align::Left,
+            wrap_width: None,
+            outline: None,
+            shadow: None,
+        }
+    }
+
+    /// The space taken by the outline and shadow on every side of the text,
+    /// as `(left, top, right, bottom)`.
+    fn padding(&self) -> (u32, u32, u32, u32) {
+        let outline = self.outline.map_or(0, |(width, _)| width as i32);
+        let (sx, sy) = self.shadow.map_or((0, 0), |(offset, _)| offset);
+
+        (
+            (outline + (-sx).max(0)) as u32,
+            (outline + (-sy).max(0)) as u32,
+            (outline + sx.max(0)) as u32,
+            (outline + sy.max(0)) as u32,
+        )
+    }
+}
+
+/// A line of text, as split by the layout, along with its width in pixels.
+struct Line {
+    text: String,
+    w: u32,
+}
+
+/// Loads fonts once and remembers the textures of the strings it renders, so
+/// that text can be drawn every frame without hitting SDL_ttf every time.
+pub struct TextCache {
+    ttf: &'static Sdl2TtfContext,
+
+    /// The fonts loaded so far, by path, size and outline width.
+    fonts: HashMap<(&'static str, u16, u16), Rc<Font<'static, 'static>>>,
+
+    /// The strings rendered so far, along with the last time they were used.
+    sprites: HashMap<(String, TextStyle), (Sprite, u64)>,
+
+    /// The strings evicted from the cache while the views still used them,
+    /// whose textures are freed once the views let go of them.
+    evicted: Vec<Sprite>,
+
+    /// Incremented every time a string is requested.
+    clock: u64,
+}
+
+thread_local! {
+    //? Fonts borrow the TTF context for as long as they live. Because the
+    //? caches live as long as the program, we simply never free it, and SDL_ttf
+    //? refuses to be initialized twice, so every cache shares it.
+    static TTF: &'static Sdl2TtfContext = Box::leak(Box::new(::sdl2::ttf::init().unwrap()));
+}
+
+impl TextCache {
+    pub fn new() -> TextCache {
+        TextCache {
+            ttf: TTF.with(|ttf| *ttf),
+            fonts: HashMap::new(),
+            sprites: HashMap::new(),
+            evicted: vec![],
+            clock: 0,
+        }
+    }
+
+    /// Returns the font at `path`, loading it if it was never used before.
+    fn font(&mut self, path: &'static str, size: u16, outline: u16) -> Option<Rc<Font<'static, 'static>>> {
+        if let Some(font) = self.fonts.get(&(path, size, outline)) {
+            return Some(font.clone());
+        }
+
+        let mut font = self.ttf.load_font(path, size).ok()?;
+        font.set_outline_width(outline);
+
+        let font = Rc::new(font);
+        self.fonts.insert((path, size, outline), font.clone());
+        Some(font)
+    }
+
+    /// Splits `text` into lines, at every line feed and wherever a line would
+    /// be wider than `style.wrap_width`.
+    fn layout(font: &Font, text: &str, style: &TextStyle) -> Vec<Line> {
+        let width_of = |line: &str| font.size_of(line).map(|(w, _)| w).unwrap_or(0);
+        let mut lines = vec![];
+
+        for paragraph in text.split('\n') {
+            let max_w = match style.wrap_width {
+                Some(max_w) => max_w,
+                None => {
+                    lines.push(Line { text: paragraph.to_string(), w: width_of(paragraph) });
+                    continue;
+                },
+            };
+
+            let mut current = String::new();
+
+            for word in paragraph.split(' ') {
+                let candidate =
+                    if current.is_empty() { word.to_string() }
+                    else { format!("{} {}", current, word) };
+
+                // A word which does not fit on its own line is not broken.
+                if width_of(&candidate) > max_w && !current.is_empty() {
+                    let w = width_of(&current);
+                    lines.push(Line { text: current, w });
+                    current = word.to_string();
+                } else {
+                    current = candidate;
+                }
+            }
+
+            let w = width_of(&current);
+            lines.push(Line { text: current, w });
+        }
+
+        lines
+    }
+
+    /// Computes the size, in pixels, that `text` would take once rendered
+    /// with the given style, without rendering it.
+    pub fn measure(&mut self, text: &str, style: &TextStyle) -> Option<(f64, f64)> {
+        let font = self.font(style.font, style.size, 0)?;
+        let lines = TextCache::layout(&font, text, style);
+        let (w, h) = TextCache::block_size(&font, &lines);
+        let (left, top, right, bottom) = style.padding();
+
+        Some(((w + left + right) as f64, (h + top + bottom) as f64))
+    }
+
+    /// The size of a block made of the given `lines`, without any padding.
+    fn block_size(font: &Font, lines: &[Line]) -> (u32, u32) {
+        let w = lines.iter().map(|line| line.w).max().unwrap_or(0);
+        let h = font.recommended_line_spacing() * (lines.len() as i32 - 1) + font.height();
+        (w, h.max(0) as u32)
+    }
+
+    /// Returns a sprite showing `text` rendered with the given style. Strings
+    /// which were recently rendered are reused rather than rendered again.
+    pub fn sprite(&mut self, renderer: &WindowCanvas, text: &str, style: &TextStyle) -> Option<Sprite> {
+        self.clock += 1;
+        let key = (text.to_string(), *style);
+
+        if let Some(entry) = self.sprites.get_mut(&key) {
+            entry.1 = self.clock;
+            return Some(entry.0.clone());
+        }
+
+        let surface = self.render(text, style)?;
+        let sprite = renderer.texture_creator()
+            .create_texture_from_surface(&surface).ok()
+            .map(Sprite::new)?;
+
+        if self.sprites.len() >= TEXT_CACHE_CAPACITY {
+            let oldest = self.sprites.iter()
+                .min_by_key(|&(_, &(_, last_used))| last_used)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            if let Some((sprite, _)) = self.sprites.remove(&oldest) {
+                self.evicted.push(sprite);
+            }
+        }
+
+        self.evicted = self.evicted.drain(..)
+            .filter_map(|sprite| sprite.destroy().err())
+            .collect();
+
+        self.sprites.insert(key, (sprite.clone(), self.clock));
+        Some(sprite)
+    }
+
+    /// Renders every layer of `text` (shadow, outline and glyphs) to a single
+    /// surface.
+    fn render(&mut self, text: &str, style: &TextStyle) -> Option<Surface<'static>> {
+        let font = self.font(style.font, style.size, 0)?;
+        let outline = match style.outline {
+            Some((width, color)) => Some((self.font(style.font, style.size, width)?, width as i32, color)),
+            None => None,
+        };
+
+        let lines = TextCache::layout(&font, text, style);
+        let (block_w, block_h) = TextCache::block_size(&font, &lines);
+        let (left, top, right, bottom) = style.padding();
+        let line_skip = font.recommended_line_spacing();
+
+        let mut target = Surface::new(
+            (block_w + left + right).max(1),
+            (block_h + top + bottom).max(1),
+            PixelFormatEnum::RGBA32).ok()?;
+
+        // Every layer is drawn at an offset from where the glyphs go.
+        let mut layers = vec![];
+        if let Some((offset, color)) = style.shadow {
+            match outline {
+                Some((ref font, width, _)) => layers.push((font.clone(), color, offset.0 - width, offset.1 - width)),
+                None => layers.push((font.clone(), color, offset.0, offset.1)),
+            }
+        }
+        if let Some((ref font, width, color)) = outline {
+            layers.push((font.clone(), color, -width, -width));
+        }
+        layers.push((font.clone(), style.color, 0, 0));
+
+        for (font, color, dx, dy) in layers {
+            for (i, line) in lines.iter().enumerate() {
+                // SDL_ttf refuses to render empty strings.
+                if line.text.is_empty() {
+                    continue;
+                }
+
+                let x = match style.align {
+                    Align::Left => 0,
+                    Align::Center => (block_w - line.w) / 2,
+                    Align::Right => block_w - line.w,
+                } as i32 + left as i32 + dx;
+                let y = i as i32 * line_skip + top as i32 + dy;
+
+                let surface = font.render(&line.text).blended(color).ok()?;
+                let (w, h) = surface.size();
+                surface.blit(None, &mut target, SdlRect::new(x, y, w, h)).ok()?;
+            }
+        }
+
+        Some(target)
+    }
+}
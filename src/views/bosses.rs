@@ -0,0 +1,387 @@
+use crate::phi::Phi;
+use crate::phi::atlas::Atlas;
+use crate::phi::data::{Rectangle, Vec2};
+use crate::phi::ecs::{Collider, Entity, Health, Lifetime, Transform, Velocity, World};
+use crate::phi::gfx::Animator;
+use crate::views::bullets::spawn_enemy_bullet;
+use crate::views::enemies::enemy_hitbox;
+use crate::views::entities::Kind;
+use rand::Rng;
+use sdl2::pixels::Color;
+
+/// Bosses are huge versions of the ships, carrying smaller ones as turrets.
+const BOSS_PATH: &str = "assets/spaceship.ron";
+
+/// The size of the boss, and of its turrets, relative to the player's ship.
+const BOSS_SCALE: f64 = 4.0;
+const TURRET_SCALE: f64 = 1.3;
+
+/// Where the top-left corner of every turret is, relative to the boss.
+const TURRET_POSITIONS: [(f64, f64); 2] = [(0.45, -0.1), (0.45, 0.77)];
+
+/// The number of bullets it takes to destroy the boss, and each turret.
+const BOSS_HEALTH: u32 = 40;
+const TURRET_HEALTH: u32 = 10;
+
+/// The score earned by destroying the boss, and each turret.
+pub const BOSS_POINTS: u32 = 50;
+pub const TURRET_POINTS: u32 = 10;
+
+/// The speed at which the boss comes in, in pixels per second, and how far
+/// from the right of the screen it stops.
+const BOSS_SPEED: f64 = 80.0;
+const BOSS_MARGIN: f64 = 40.0;
+
+/// How far, in pixels, the boss goes above and below the middle of the
+/// screen, and how fast it does so, in radians per second. It swings faster
+/// once enraged.
+const SWING_AMPLITUDE: f64 = 120.0;
+const SWING_ANGULAR_VEL: f64 = 0.8;
+const ENRAGED_PACE: f64 = 1.75;
+
+/// How long the boss holds its fire when it enters a new phase, in seconds.
+const PHASE_PAUSE: f64 = 1.0;
+
+/// The time between two shots of a turret, in seconds.
+const TURRET_RELOAD: f64 = 1.2;
+
+/// The fan of bullets shot at the player once the turrets are destroyed: how
+/// many, how far apart, in degrees, and how often, in seconds.
+const FAN_BULLETS: usize = 5;
+const FAN_SPREAD: f64 = 15.0;
+const FAN_RELOAD: f64 = 1.5;
+
+/// The ring of bullets shot all around once enraged, which turns by
+/// `RING_TURN` degrees between two volleys.
+const RING_BULLETS: usize = 12;
+const RING_TURN: f64 = 10.0;
+const RING_RELOAD: f64 = 0.7;
+
+/// How long the wreck of a boss burns, in seconds, before its last explosion,
+/// and the time between the explosions going off on it until then.
+const WRECK_TIME: f64 = 2.5;
+const BLAST_INTERVAL: f64 = 0.15;
+
+/// How the wreck drifts while it burns, in pixels and degrees per second.
+const WRECK_DRIFT: Vec2 = Vec2 { x: -20.0, y: 40.0 };
+const WRECK_SPIN: f64 = -15.0;
+
+/// The stages of a boss fight, each with its own way of shooting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// The turrets shoot at the player, while they shield the boss.
+    Shielded,
+
+    /// The turrets are destroyed, and the boss shoots fans of bullets.
+    Exposed,
+
+    /// The boss lost half of its health, and shoots rings of bullets.
+    Enraged,
+}
+
+/// The body of a boss of the world, which carries its turrets.
+#[derive(Clone, Copy)]
+pub struct Boss {
+    pub phase: Phase,
+    pub max_health: u32,
+
+    /// The height of the middle of the boss's swing, and where it stops
+    /// horizontally.
+    origin_y: f64,
+    hover_x: f64,
+    arrived: bool,
+
+    /// How far the boss is into its swing, in radians.
+    swing: f64,
+
+    /// How fast the boss and its bullets move, relative to their usual speed.
+    pace: f64,
+
+    /// The time left, in seconds, before the next volley, and the number of
+    /// volleys since the boss arrived.
+    reload: f64,
+    volleys: u32,
+}
+
+impl Boss {
+    /// The color by which the sprite of the boss is tinted.
+    pub fn tint(&self) -> Color {
+        match self.phase {
+            Phase::Shielded | Phase::Exposed => Color::RGB(200, 140, 255),
+            Phase::Enraged => Color::RGB(255, 110, 160),
+        }
+    }
+
+    /// Enter the phase which the remaining `health`, and whether the boss still
+    /// has turrets, call for.
+    fn update_phase(&mut self, has_turrets: bool, health: u32) {
+        let phase =
+            if has_turrets { Phase::Shielded }
+            else if health * 2 <= self.max_health { Phase::Enraged }
+            else { Phase::Exposed };
+
+        if phase != self.phase {
+            self.phase = phase;
+            self.reload = PHASE_PAUSE;
+        }
+    }
+
+    /// Choose the velocity of the boss, which is at `rect`, for the next `dt`
+    /// seconds: it comes in, then swings up and down.
+    fn steer(&mut self, rect: Rectangle, dt: f64) -> Vec2 {
+        if !self.arrived {
+            if rect.x > self.hover_x {
+                return Vec2::new(-BOSS_SPEED * self.pace, 0.0);
+            }
+            self.arrived = true;
+        }
+
+        let rage = if self.phase == Phase::Enraged { ENRAGED_PACE } else { 1.0 };
+        self.swing += SWING_ANGULAR_VEL * rage * self.pace * dt;
+
+        // Head for where the swing is at the end of the frame.
+        let target_y = self.origin_y + SWING_AMPLITUDE * self.swing.sin();
+        Vec2::new(0.0, if dt == 0.0 { 0.0 } else { (target_y - rect.center().y) / dt })
+    }
+
+    /// The directions of the bullets shot by the boss itself, from `pos`,
+    /// during this frame, lasting `dt` seconds.
+    fn volley(&mut self, pos: Vec2, player: Vec2, dt: f64) -> Vec<Vec2> {
+        if !self.arrived || self.phase == Phase::Shielded {
+            return vec![];
+        }
+
+        self.reload -= dt;
+        if self.reload > 0.0 {
+            return vec![];
+        }
+        self.volleys += 1;
+
+        match self.phase {
+            Phase::Shielded => vec![],
+
+            Phase::Exposed => {
+                self.reload += FAN_RELOAD;
+                let aim = player - pos;
+                let first = -FAN_SPREAD * (FAN_BULLETS - 1) as f64 / 2.0;
+                (0..FAN_BULLETS)
+                    .map(|i| aim.rotate(first + FAN_SPREAD * i as f64))
+                    .collect()
+            },
+
+            Phase::Enraged => {
+                self.reload += RING_RELOAD;
+                let turn = RING_TURN * self.volleys as f64;
+                (0..RING_BULLETS)
+                    .map(|i| Vec2::new(-1.0, 0.0).rotate(turn + 360.0 * i as f64 / RING_BULLETS as f64))
+                    .collect()
+            },
+        }
+    }
+}
+
+/// A turret of a boss, which is destroyed separately, and shields the boss
+/// until it is.
+#[derive(Clone, Copy)]
+pub struct BossPart {
+    pub boss: Entity,
+
+    /// The time left, in seconds, before it may shoot again.
+    reload: f64,
+}
+
+impl BossPart {
+    pub const TINT: Color = Color::RGB(255, 170, 90);
+}
+
+/// What remains of a boss, on which explosions go off until it blows up.
+#[derive(Clone, Copy)]
+pub struct Wreck {
+    /// The time left, in seconds, before the next explosion.
+    next_blast: f64,
+}
+
+impl Wreck {
+    pub const TINT: Color = Color::RGB(120, 60, 60);
+}
+
+/// Move the bosses of the `world` and their turrets, and make them shoot at
+/// the player, whose ship is centered on `player`. The turrets of the bosses
+/// which were destroyed are destroyed as well.
+pub fn steer_bosses(world: &mut World<Kind>, player: Vec2, dt: f64) {
+    // The bosses which still have turrets, and the turrets which lost their
+    // boss.
+    let mut shielded = vec![];
+    let mut orphans = vec![];
+    for (entity, kind) in world.kinds.iter() {
+        if let Kind::BossPart(part) = kind {
+            if world.kinds.get(part.boss).is_some() {
+                shielded.push(part.boss);
+            } else {
+                orphans.push(entity);
+            }
+        }
+    }
+
+    for part in orphans {
+        if let Some(health) = world.healths.get_mut(part) {
+            health.points = 0;
+        }
+    }
+
+    let mut shots = vec![];
+    let mut bosses = vec![];
+
+    for (entity, kind) in world.kinds.iter_mut() {
+        let boss = match *kind {
+            Kind::Boss(ref mut boss) => boss,
+            _ => continue,
+        };
+
+        let (transform, velocity, health) = match (
+            world.transforms.get(entity), world.velocities.get_mut(entity), world.healths.get(entity)
+        ) {
+            (Some(transform), Some(velocity), Some(health)) => (transform, velocity, health),
+            _ => continue,
+        };
+
+        boss.update_phase(shielded.contains(&entity), health.points);
+        velocity.vel = boss.steer(transform.rect, dt);
+
+        let nose = Vec2::new(transform.rect.x + transform.rect.w * 0.05, transform.rect.center().y);
+        for direction in boss.volley(nose, player, dt) {
+            shots.push((entity, nose, direction, boss.pace));
+        }
+
+        bosses.push((entity, velocity.vel, boss.arrived && boss.phase == Phase::Shielded, boss.pace));
+    }
+
+    // The turrets follow their boss, and shoot at the player while they
+    // shield it.
+    for (entity, kind) in world.kinds.iter_mut() {
+        let part = match *kind {
+            Kind::BossPart(ref mut part) => part,
+            _ => continue,
+        };
+
+        let (vel, firing, pace) = match bosses.iter().find(|&&(boss, _, _, _)| boss == part.boss) {
+            Some(&(_, vel, firing, pace)) => (vel, firing, pace),
+            None => continue,
+        };
+
+        let (transform, velocity) = match (world.transforms.get(entity), world.velocities.get_mut(entity)) {
+            (Some(transform), Some(velocity)) => (transform, velocity),
+            _ => continue,
+        };
+        velocity.vel = vel;
+
+        if firing {
+            part.reload -= dt;
+            if part.reload <= 0.0 {
+                part.reload += TURRET_RELOAD;
+                let nose = Vec2::new(transform.rect.x, transform.rect.center().y);
+                shots.push((entity, nose, player - nose, pace));
+            }
+        }
+    }
+
+    for (shooter, pos, direction, pace) in shots {
+        spawn_enemy_bullet(world, shooter, pos, direction, pace);
+    }
+}
+
+/// Burn the wrecks of the `world` for `dt` seconds. Returns where explosions
+/// go off on them during that time.
+pub fn chain_explosions<R: Rng>(world: &mut World<Kind>, rng: &mut R, dt: f64) -> Vec<Rectangle> {
+    let mut blasts = vec![];
+
+    for (entity, kind) in world.kinds.iter_mut() {
+        let (wreck, transform) = match (kind, world.transforms.get(entity)) {
+            (Kind::Wreck(wreck), Some(transform)) => (wreck, transform),
+            _ => continue,
+        };
+
+        wreck.next_blast -= dt;
+        while wreck.next_blast <= 0.0 {
+            wreck.next_blast += BLAST_INTERVAL;
+
+            let rect = transform.rect;
+            let at = Vec2::new(rect.x + rng.gen::<f64>() * rect.w, rect.y + rng.gen::<f64>() * rect.h);
+            blasts.push(Rectangle::with_size(rect.w / 3.0, rect.h / 3.0).center_at(at));
+        }
+    }
+
+    blasts
+}
+
+pub struct BossFactory {
+    animator: Animator,
+}
+
+impl BossFactory {
+    pub fn new(phi: &mut Phi) -> BossFactory {
+        let atlas = Atlas::load(&phi.renderer, BOSS_PATH).unwrap();
+
+        BossFactory {
+            animator: atlas.animator("mid_norm").unwrap(),
+        }
+    }
+
+    /// Add a boss and its turrets to the `world`, coming from the right of the
+    /// screen at `pace` times its usual speed.
+    pub fn spawn(&self, phi: &mut Phi, world: &mut World<Kind>, pace: f64) {
+        let (w, h) = phi.output_size();
+        let (sprite_w, sprite_h) = self.animator.size();
+        let (boss_w, boss_h) = (sprite_w * BOSS_SCALE, sprite_h * BOSS_SCALE);
+        let rect = Rectangle { x: w, y: (h - boss_h) / 2.0, w: boss_w, h: boss_h };
+        let vel = Vec2::new(-BOSS_SPEED * pace, 0.0);
+
+        let boss = world.spawn(Kind::Boss(Boss {
+            phase: Phase::Shielded,
+            max_health: BOSS_HEALTH,
+            origin_y: h / 2.0,
+            hover_x: w - rect.w - BOSS_MARGIN,
+            arrived: false,
+            swing: 0.0,
+            pace,
+            reload: 0.0,
+            volleys: 0,
+        }));
+        self.insert_ship(world, boss, rect, vel, BOSS_HEALTH);
+
+        for (i, &(x, y)) in TURRET_POSITIONS.iter().enumerate() {
+            let part = world.spawn(Kind::BossPart(BossPart {
+                boss,
+                // The turrets take turns shooting.
+                reload: TURRET_RELOAD * (i + 1) as f64 / TURRET_POSITIONS.len() as f64,
+            }));
+            self.insert_ship(world, part, Rectangle {
+                x: rect.x + x * rect.w,
+                y: rect.y + y * rect.h,
+                w: sprite_w * TURRET_SCALE,
+                h: sprite_h * TURRET_SCALE,
+            }, vel, TURRET_HEALTH);
+        }
+    }
+
+    fn insert_ship(&self, world: &mut World<Kind>, entity: Entity, rect: Rectangle, vel: Vec2, health: u32) {
+        world.animators.insert(entity, self.animator.clone());
+        world.transforms.insert(entity, Transform { rect, angle: 0.0 });
+        world.velocities.insert(entity, Velocity { vel, spin: 0.0 });
+        world.colliders.insert(entity, Collider {
+            shape: enemy_hitbox(rect.w, rect.h),
+            swept: false,
+        });
+        world.healths.insert(entity, Health { points: health });
+    }
+
+    /// Add to the `world` the wreck of a boss which was destroyed at
+    /// `transform`. It burns until its lifetime is over, when it blows up.
+    pub fn spawn_wreck(&self, world: &mut World<Kind>, transform: Transform) {
+        let wreck = world.spawn(Kind::Wreck(Wreck { next_blast: 0.0 }));
+        world.animators.insert(wreck, self.animator.clone());
+        world.transforms.insert(wreck, transform);
+        world.velocities.insert(wreck, Velocity { vel: WRECK_DRIFT, spin: WRECK_SPIN });
+        world.lifetimes.insert(wreck, Lifetime { remaining: WRECK_TIME });
+    }
+}
@@ -1,229 +1,202 @@
-use crate::phi::Phi;
-use crate::phi::data::Rectangle;
+use crate::phi::data::{Rectangle, Shape, Vec2};
+use crate::phi::draw::{DrawList, Layer};
+use crate::phi::ecs::{Collider, Entity, Health, Transform, Velocity, World};
+use crate::views::entities::Kind;
+use crate::views::shared::Z_BULLETS;
 use sdl2::pixels::Color;
 
 //? The velocity shared by all bullets, in pixels per second.
 const BULLET_SPEED: f64 = 240.0;
 
+/// The speed of the bullets shot by enemies, slow enough to be dodged.
+const ENEMY_BULLET_SPEED: f64 = 180.0;
+
 //? The size of the rectangle which will represent the bullet.
 const BULLET_W: f64 = 8.0;
 const BULLET_H: f64 = 4.0;
 
-struct RectBullet {
-    rect: Rectangle,
+/// The side of a ship, and of the bullets it shoots, which only hit the other
+/// side unless friendly fire is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
 }
 
-struct SineBullet {
-    //? Notice that the bounding box isn't stored directly. This means that
-    //? we do not keep useless innformation. It also implies that we must compute
-    //? the `sin` function every time we attempt to get the bounding box.
-    pos_x: f64,
-    origin_y: f64,
-    amplitude: f64, 
-    angular_vel: f64,
-    total_time: f64,
-}
+/// A bullet of the world.
+#[derive(Clone, Copy)]
+pub struct Bullet {
+    pub path: BulletPath,
+    pub owner: Faction,
 
-/// Bullet which follows a vertical trajectory given by:
-///     a * ((t / b)^3 - (t / b)^2)
-struct DevergentBullet {
-    pos_x : f64,
-    origin_y : f64,
-    a: f64,
-    b: f64,
-    total_time: f64,
+    /// The ship which shot the bullet, and which it never hits.
+    pub shooter: Entity,
 }
 
-pub trait Bullet: {
-    /// Update the bullet.
-    /// If the bullet should be destroyed, e.g. because it has left the screen
-    /// then return `None`.
-    /// Otherwise, return `Some(update_bullet)`
-    /// 
-    /// Notice how we use `Box<Self> as the type of `self`. This means: keep
-    /// this data behind a pointer, but `move` the pointer. You should note that
-    /// we are not copying the value: we are only copying the _address_ at
-    /// which the value is stored in memory, which has a negligible cost. We can
-    /// do this because Rust will automatically free the memory once the `Box` that
-    /// contains it is itself destroyed.
-    fn update(self: Box<Self>, phi: &mut Phi, dt: f64) -> Option<Box<dyn Bullet>>;
-
-    /// Render the bullet to the screen.
-    /// Here, we take an immutable reference to the bullet, because we do not
-    /// need to change its value to draw it, This is the same as before.
-    fn render(&self, phi: &mut Phi);
-
-    /// Return the bullet's bounding box.
-    fn rect(&self) -> Rectangle;
+/// How a bullet moves vertically, while flying straight ahead. Only those of
+/// the player follow a path.
+#[derive(Clone, Copy)]
+pub enum BulletPath {
+    Straight,
+
+    /// Bullet which oscillates around the height at which it was shot:
+    ///     amplitude * sin(angular_vel * t)
+    Sine { amplitude: f64, angular_vel: f64, time: f64 },
+
+    /// Bullet which follows a vertical trajectory given by:
+    ///     a * ((t / b)^3 - (t / b)^2)
+    Devergent { a: f64, b: f64, time: f64 },
 }
 
-impl Bullet for RectBullet {
-    /// Update the bullet.
-    /// If the bullet should be destroyed, e.g. because it has left the screen
-    /// then return `None`.
-    /// Otherwise, return `Some(update_bullet)`
-    fn update(mut self: Box<Self>, phi: &mut Phi, dt: f64) -> Option<Box<dyn Bullet>> {
-        let (w, _) = phi.output_size();
-        self.rect.x += BULLET_SPEED * dt;
-
-        // If the bullet has left the screen then delete it.
-        if self.rect.x > w {
-            None
-        } else {
-            Some(self)
+impl BulletPath {
+    /// How far below the height at which it was shot the bullet is.
+    fn offset(&self) -> f64 {
+        match *self {
+            BulletPath::Straight => 0.0,
+            BulletPath::Sine { amplitude, angular_vel, time } =>
+                amplitude * f64::sin(angular_vel * time),
+            BulletPath::Devergent { a, b, time } =>
+                a * ((time / b).powi(3) - (time / b).powi(2)),
         }
     }
 
-    /// Render the bullet to the screen.
-    fn render(&self, phi: &mut Phi) {
-        // We will render this kind of bullet in yellow
-        phi.renderer.set_draw_color(Color::RGB(230, 230, 30));
-        phi.renderer.fill_rect(self.rect.to_sdl()).unwrap();
+    /// Move along the path for `dt` seconds. Returns the vertical velocity
+    /// which gets the bullet there during that time, unless the bullet keeps
+    /// the velocity it was shot with.
+    fn steer(&mut self, dt: f64) -> Option<f64> {
+        let before = self.offset();
+        match *self {
+            BulletPath::Straight => return None,
+            BulletPath::Sine { ref mut time, .. } |
+            BulletPath::Devergent { ref mut time, .. } => *time += dt,
+        }
+
+        Some(if dt == 0.0 { 0.0 } else { (self.offset() - before) / dt })
     }
+}
 
-    /// Return the bullet's bounding box.
-    fn rect(&self) -> Rectangle {
-        self.rect
+/// Set the velocity of every bullet of the `world` so that, once the world
+/// moves its entities by `dt` seconds, they are where their path leads.
+pub fn steer_bullets(world: &mut World<Kind>, dt: f64) {
+    for (bullet, kind) in world.kinds.iter_mut() {
+        if let Kind::Bullet(Bullet { ref mut path, .. }) = *kind {
+            if let (Some(vel_y), Some(velocity)) = (path.steer(dt), world.velocities.get_mut(bullet)) {
+                velocity.vel.y = vel_y;
+            }
+        }
     }
 }
 
-impl Bullet for SineBullet {
-    fn update(mut self: Box<Self>, phi: &mut Phi, dt: f64) -> Option<Box<dyn Bullet>> {
-        //? We store the total time...
-        self.total_time += dt;
+/// Render a bullet of the given bounding box to the screen.
+pub fn render_bullet(list: &mut DrawList, rect: Rectangle, owner: Faction) {
+    // We will render the player's bullets in yellow, and the others in red.
+    let color = match owner {
+        Faction::Player => Color::RGB(230, 230, 30),
+        Faction::Enemy => Color::RGB(250, 70, 40),
+    };
+    list.fill(Layer::Scene, Z_BULLETS, rect, color);
+}
 
-        //? And move at the same speed as regular bullets.
-        self.pos_x += BULLET_SPEED * dt;
+/// Add to the `world` a bullet shot by the enemy `shooter` from `pos`, its
+/// center, in the given `direction`, or to the left if there is none, e.g.
+/// when aiming at a player right on top of the shooter. It flies at `pace`
+/// times the usual speed of enemy bullets.
+pub fn spawn_enemy_bullet(world: &mut World<Kind>, shooter: Entity, pos: Vec2, direction: Vec2, pace: f64) {
+    let direction =
+        if direction == Vec2::default() { Vec2::new(-1.0, 0.0) }
+        else { direction.normalize() };
+
+    spawn_bullet(world, Bullet { path: BulletPath::Straight, owner: Faction::Enemy, shooter },
+        Rectangle::with_size(BULLET_H, BULLET_H).center_at(pos),
+        direction * ENEMY_BULLET_SPEED * pace);
+}
 
-        // If the bullet has left the screen, then delete it.
-        let (w, _) = phi.output_size();
+fn spawn_bullet(world: &mut World<Kind>, bullet: Bullet, rect: Rectangle, vel: Vec2) {
+    let entity = world.spawn(Kind::Bullet(bullet));
+    world.transforms.insert(entity, Transform { rect, angle: 0.0 });
+    world.velocities.insert(entity, Velocity { vel, spin: 0.0 });
+    world.colliders.insert(entity, Collider {
+        shape: Shape::Rect(Rectangle::with_size(rect.w, rect.h)),
+        swept: true,
+    });
+    world.healths.insert(entity, Health { points: 1 });
+}
 
-        if self.rect().x > w{
-            None
-        } else {
-            Some(self)
-        }
-    }
+#[derive(Clone,Copy)]
+pub enum CannonType {
+    RectBullet,
+    SineBullet { amplitude: f64, angular_vel: f64 },
+    DevergentBullet { a: f64, b: f64},
+}
 
-    fn render(&self, phi: &mut Phi) {
-        phi.renderer.set_draw_color(Color::RGB(230, 230, 30));
-        phi.renderer.fill_rect(self.rect().to_sdl()).unwrap();
+impl CannonType {
+    /// A short description of the cannon and its parameters.
+    pub fn describe(&self) -> String {
+        match *self {
+            CannonType::RectBullet =>
+                "RECT".to_string(),
+            CannonType::SineBullet { amplitude, angular_vel } =>
+                format!("SINE AMPLITUDE {} SPEED {}", amplitude, angular_vel),
+            CannonType::DevergentBullet { a, b } =>
+                format!("DIVERGENT A {} B {}", a, b),
+        }
     }
+}
 
-    fn rect(&self) -> Rectangle {
-        //? Just the general form of the sine function, minus the initial time.
-        let dy = self.amplitude * f64::sin(self.angular_vel * self.total_time);
-        Rectangle {
-            x: self.pos_x,
-            y: self.origin_y + dy,
-            w: BULLET_W,
-            h: BULLET_H,
-        }
+/// Add to the `world` the bullets shot by `cannon`, one from each side of the
+/// player's ship, `shooter`.
+pub fn spawn_bullets(world: &mut World<Kind>, shooter: Entity, cannon: CannonType, cannons_x: f64, cannons1_y: f64, cannons2_y: f64) {
+    let paths = match cannon {
+        CannonType::RectBullet =>
+            [BulletPath::Straight, BulletPath::Straight],
+
+        CannonType::SineBullet { amplitude, angular_vel } => {
+            let path = BulletPath::Sine { amplitude, angular_vel, time: 0.0 };
+            [path, path]
+        },
+
+        CannonType::DevergentBullet { a, b } => [
+            // If a,b > 0, eventually goes upwards
+            BulletPath::Devergent { a: -a, b, time: 0.0 },
+            // If a,b > 0, eventually goes downwards
+            BulletPath::Devergent { a, b, time: 0.0 },
+        ],
+    };
+
+    for (path, y) in paths.into_iter().zip([cannons1_y, cannons2_y]) {
+        spawn_bullet(world, Bullet { path, owner: Faction::Player, shooter },
+            Rectangle { x: cannons_x, y, w: BULLET_W, h: BULLET_H },
+            Vec2::new(BULLET_SPEED, 0.0));
     }
 }
 
-impl Bullet for DevergentBullet {
-    fn update(mut self: Box<Self>, phi: &mut Phi, dt: f64) -> Option<Box<dyn Bullet>>{
-        self.total_time += dt;
-        self.pos_x += BULLET_SPEED * dt;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // If the bullet has left the screen, then delete it.
-        let (w, h) = phi.output_size();
-        let rect = self.rect();
+    fn bullet_velocity(direction: Vec2, pace: f64) -> Vec2 {
+        let mut world = World::new();
+        let shooter = world.spawn(Kind::Asteroid);
+        spawn_enemy_bullet(&mut world, shooter, Vec2::new(100.0, 100.0), direction, pace);
 
-        if rect.x > w || rect.x < 0.0 ||
-           rect.y > h || rect.y < 0.0 {
-            None
-        } else {
-            Some(self)
-        }
+        let (bullet, _) = world.kinds.iter().find(|(_, kind)| matches!(kind, Kind::Bullet(_))).unwrap();
+        world.velocities.get(bullet).unwrap().vel
     }
 
-    fn render(&self, phi: &mut Phi) {
-        // We will render this kind of bullet in yellow.
-        phi.renderer.set_draw_color(Color::RGB(230, 230, 30));
-        phi.renderer.fill_rect(self.rect().to_sdl()).unwrap();
+    #[test]
+    fn enemy_bullets_fly_at_the_same_speed_in_every_direction() {
+        assert_eq!(bullet_velocity(Vec2::new(0.0, 5.0), 1.0), Vec2::new(0.0, ENEMY_BULLET_SPEED));
+        assert_eq!(bullet_velocity(Vec2::new(-30.0, 40.0), 1.0), Vec2::new(-0.6, 0.8) * ENEMY_BULLET_SPEED);
     }
 
-    fn rect(&self) -> Rectangle {
-        let dy = self.a * 
-                            ((self.total_time / self.b).powi(3) - 
-                             (self.total_time / self.b).powi(2));
-        Rectangle {
-            x: self.pos_x,
-            y: self.origin_y + dy,
-            w: BULLET_W,
-            h: BULLET_H,
-        }
+    #[test]
+    fn enemy_bullets_keep_the_pace_of_the_game() {
+        assert_eq!(bullet_velocity(Vec2::new(-1.0, 0.0), 1.5), Vec2::new(-1.5 * ENEMY_BULLET_SPEED, 0.0));
+        assert_eq!(bullet_velocity(Vec2::new(0.0, 3.0), 0.5), Vec2::new(0.0, 0.5 * ENEMY_BULLET_SPEED));
     }
-}
-
-#[derive(Clone,Copy)]
-pub enum CannonType {
-    RectBullet,
-    SineBullet { amplitude: f64, angular_vel: f64 },
-    DevergentBullet { a: f64, b: f64},
-}
 
-pub fn spawn_bullets(cannon: CannonType, cannons_x: f64, cannons1_y: f64, cannons2_y: f64) -> Vec<Box<dyn Bullet>> {
-    match cannon {
-        CannonType::RectBullet => 
-            vec![
-                Box::new(RectBullet {
-                    rect: Rectangle {
-                        x: cannons_x,
-                        y: cannons1_y,
-                        w: BULLET_W,
-                        h: BULLET_H,
-                    }
-                }),
-                Box::new(RectBullet {
-                    rect: Rectangle {
-                        x: cannons_x,
-                        y: cannons2_y,
-                        w: BULLET_W,
-                        h: BULLET_H,
-                    }
-                }),
-            ],
-
-        CannonType::SineBullet { amplitude, angular_vel } =>
-            vec![
-                Box::new(SineBullet {
-                    pos_x: cannons_x,
-                    origin_y: cannons1_y,
-                    amplitude: amplitude,
-                    angular_vel: angular_vel,
-                    total_time: 0.0,
-                }),
-                Box::new(SineBullet {
-                    pos_x: cannons_x,
-                    origin_y: cannons2_y,
-                    amplitude: amplitude,
-                    angular_vel: angular_vel,
-                    total_time: 0.0,
-                }),
-            ],
-            
-        CannonType::DevergentBullet { a, b } => 
-            vec![
-                // If a,b > 0, eventually goes upwards
-                Box::new(DevergentBullet {
-                    pos_x: cannons_x,
-                    origin_y: cannons1_y,
-                    a: -a,
-                    b: b, 
-                    total_time: 0.0,
-                }),
-
-                // If a,b > 0, eventually goes downwards
-                Box::new(DevergentBullet{
-                    pos_x: cannons_x,
-                    origin_y: cannons2_y,
-                    a: a,
-                    b: b,
-                    total_time: 0.0,
-                })
-            ]
+    #[test]
+    fn enemy_bullets_without_a_direction_fly_to_the_left() {
+        assert_eq!(bullet_velocity(Vec2::default(), 1.0), Vec2::new(-ENEMY_BULLET_SPEED, 0.0));
     }
 }
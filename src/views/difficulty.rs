@@ -0,0 +1,164 @@
+/// The bounds of the adjustment made by adaptive difficulty, relative to the
+/// preset.
+const ADAPTIVE_MIN: f64 = 0.7;
+const ADAPTIVE_MAX: f64 = 1.4;
+
+/// How much harder the game gets whenever the player destroys something, and
+/// how much easier whenever the player is hit.
+const KILL_BOOST: f64 = 0.02;
+const HIT_EASING: f64 = 0.15;
+
+/// The levels of difficulty to choose from in the menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Preset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Easy => "Easy",
+            Preset::Normal => "Normal",
+            Preset::Hard => "Hard",
+        }
+    }
+
+    /// The preset which follows this one in the menu.
+    pub fn next(self) -> Preset {
+        match self {
+            Preset::Easy => Preset::Normal,
+            Preset::Normal => Preset::Hard,
+            Preset::Hard => Preset::Easy,
+        }
+    }
+
+    /// How fast asteroids and enemies move, relative to their usual speed.
+    fn pace(self) -> f64 {
+        match self {
+            Preset::Easy => 0.75,
+            Preset::Normal => 1.0,
+            Preset::Hard => 1.3,
+        }
+    }
+
+    /// How fast the waves of a stage follow each other, relative to their
+    /// script.
+    fn spawn_rate(self) -> f64 {
+        match self {
+            Preset::Easy => 0.75,
+            Preset::Normal => 1.0,
+            Preset::Hard => 1.35,
+        }
+    }
+}
+
+/// The difficulty chosen in the menu, which, if adaptive, is then adjusted
+/// during the game to how well the player does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difficulty {
+    pub preset: Preset,
+    pub adaptive: bool,
+
+    /// The adjustment made to the preset, 1.0 leaving it unchanged.
+    level: f64,
+}
+
+impl Difficulty {
+    pub fn new(preset: Preset, adaptive: bool) -> Difficulty {
+        Difficulty { preset, adaptive, level: 1.0 }
+    }
+
+    /// How fast asteroids and enemies move, relative to their usual speed.
+    pub fn pace(&self) -> f64 {
+        self.preset.pace() * self.level
+    }
+
+    /// How fast the waves of a stage follow each other, relative to their
+    /// script.
+    pub fn spawn_rate(&self) -> f64 {
+        self.preset.spawn_rate() * self.level
+    }
+
+    /// Makes the game harder, as the player destroyed something.
+    pub fn scored(&mut self) {
+        self.adjust(KILL_BOOST);
+    }
+
+    /// Makes the game easier, as the player was hit.
+    pub fn player_hit(&mut self) {
+        self.adjust(-HIT_EASING);
+    }
+
+    fn adjust(&mut self, change: f64) {
+        if self.adaptive {
+            self.level = (self.level + change).clamp(ADAPTIVE_MIN, ADAPTIVE_MAX);
+        }
+    }
+
+    /// A short description of the difficulty and of its current adjustment.
+    pub fn describe(&self) -> String {
+        if self.adaptive {
+            format!("{} ADAPTIVE x{:.2}", self.preset.name().to_uppercase(), self.level)
+        } else {
+            self.preset.name().to_uppercase()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_scale_pace_and_spawn_rate() {
+        let normal = Difficulty::new(Preset::Normal, false);
+        assert_eq!((normal.pace(), normal.spawn_rate()), (1.0, 1.0));
+
+        let easy = Difficulty::new(Preset::Easy, true);
+        assert_eq!((easy.pace(), easy.spawn_rate()), (0.75, 0.75));
+
+        let hard = Difficulty::new(Preset::Hard, false);
+        assert_eq!((hard.pace(), hard.spawn_rate()), (1.3, 1.35));
+
+        assert_eq!(Preset::Easy.next().next().next(), Preset::Easy);
+    }
+
+    #[test]
+    fn adaptive_difficulty_follows_the_player() {
+        let mut difficulty = Difficulty::new(Preset::Normal, true);
+        difficulty.scored();
+        assert_eq!(difficulty.pace(), 1.0 + KILL_BOOST);
+        assert_eq!(difficulty.spawn_rate(), 1.0 + KILL_BOOST);
+
+        difficulty.player_hit();
+        assert_eq!(difficulty.pace(), 1.0 + KILL_BOOST - HIT_EASING);
+    }
+
+    #[test]
+    fn adaptive_difficulty_stays_within_its_bounds() {
+        let mut difficulty = Difficulty::new(Preset::Hard, true);
+        for _ in 0..100 {
+            difficulty.scored();
+        }
+        assert_eq!(difficulty.level, ADAPTIVE_MAX);
+        assert_eq!(difficulty.pace(), Preset::Hard.pace() * ADAPTIVE_MAX);
+
+        for _ in 0..100 {
+            difficulty.player_hit();
+        }
+        assert_eq!(difficulty.level, ADAPTIVE_MIN);
+        assert_eq!(difficulty.spawn_rate(), Preset::Hard.spawn_rate() * ADAPTIVE_MIN);
+    }
+
+    #[test]
+    fn fixed_difficulty_ignores_the_player() {
+        let mut difficulty = Difficulty::new(Preset::Easy, false);
+        difficulty.scored();
+        difficulty.player_hit();
+        difficulty.player_hit();
+        assert_eq!(difficulty, Difficulty::new(Preset::Easy, false));
+        assert_eq!(difficulty.describe(), "EASY");
+    }
+}
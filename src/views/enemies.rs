@@ -0,0 +1,353 @@
+use crate::phi::Phi;
+use crate::phi::atlas::Atlas;
+use crate::phi::data::{Polygon, Rectangle, Shape, Vec2};
+use crate::phi::ecs::{Collider, Health, Transform, Velocity, World};
+use crate::phi::gfx::Animator;
+use crate::views::bullets::spawn_enemy_bullet;
+use crate::views::entities::Kind;
+use rand::Rng;
+use sdl2::pixels::Color;
+use serde::Deserialize;
+
+/// Enemies are ships like the player's, facing the other way and tinted.
+const ENEMY_PATH: &str = "assets/spaceship.ron";
+
+/// The outline of an enemy's hitbox, relative to its sprite, from the top of
+/// its tail, around its nose, to the bottom of its tail.
+const ENEMY_HITBOX: [(f64, f64); 5] = [
+    (0.95, 0.05), (0.4, 0.25), (0.0, 0.5), (0.4, 0.75), (0.95, 0.95),
+];
+
+/// How far, in pixels, a weaver goes above and below the height at which it
+/// appeared, and how fast it does so, in radians per second.
+const WEAVE_AMPLITUDE: f64 = 60.0;
+const WEAVE_ANGULAR_VEL: f64 = 3.0;
+
+/// How far ahead of the player, in pixels, a diver starts to dive, and its
+/// speed once it does.
+const DIVE_RANGE: f64 = 260.0;
+const DIVE_SPEED: f64 = 320.0;
+
+/// Where a strafer stops, relative to the width of the screen, and how long
+/// it stays there, in seconds, before leaving.
+const STRAFE_X: f64 = 0.8;
+const STRAFE_TIME: f64 = 6.0;
+
+/// How quickly a hunter turns towards the player, as the fraction of the way
+/// to the player's direction covered every second, and for how long it does,
+/// in seconds, before flying straight.
+const HOMING_TURN: f64 = 1.5;
+const HOMING_TIME: f64 = 5.0;
+
+/// The types of enemy ships, which differ by how they move and shoot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum EnemyType {
+    /// Flies along a sine wave, shooting straight ahead.
+    Weaver,
+
+    /// Flies straight until the player is in front of it, then rushes at
+    /// where the player was.
+    Diver,
+
+    /// Stops near the right of the screen to move up and down while shooting
+    /// at the player, then leaves.
+    Strafer,
+
+    /// Turns towards the player, to ram it.
+    Hunter,
+}
+
+impl EnemyType {
+    pub const ALL: [EnemyType; 4] = [EnemyType::Weaver, EnemyType::Diver, EnemyType::Strafer, EnemyType::Hunter];
+
+    /// The name of the type, e.g. in console commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            EnemyType::Weaver => "weaver",
+            EnemyType::Diver => "diver",
+            EnemyType::Strafer => "strafer",
+            EnemyType::Hunter => "hunter",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EnemyType> {
+        EnemyType::ALL.iter().cloned().find(|enemy_type| enemy_type.name() == name)
+    }
+
+    /// The color by which the sprite of the ship is tinted.
+    pub fn tint(self) -> Color {
+        match self {
+            EnemyType::Weaver => Color::RGB(120, 255, 120),
+            EnemyType::Diver => Color::RGB(255, 160, 60),
+            EnemyType::Strafer => Color::RGB(120, 160, 255),
+            EnemyType::Hunter => Color::RGB(255, 90, 90),
+        }
+    }
+
+    /// The number of bullets it takes to destroy the ship.
+    fn health(self) -> u32 {
+        match self {
+            EnemyType::Weaver => 1,
+            EnemyType::Diver => 2,
+            EnemyType::Strafer => 4,
+            EnemyType::Hunter => 2,
+        }
+    }
+
+    /// The score earned by destroying the ship.
+    pub fn points(self) -> u32 {
+        match self {
+            EnemyType::Weaver => 2,
+            EnemyType::Diver => 3,
+            EnemyType::Strafer => 5,
+            EnemyType::Hunter => 4,
+        }
+    }
+
+    /// The speed of the ship, in pixels per second.
+    fn speed(self) -> f64 {
+        match self {
+            EnemyType::Weaver => 120.0,
+            EnemyType::Diver => 150.0,
+            EnemyType::Strafer => 140.0,
+            EnemyType::Hunter => 170.0,
+        }
+    }
+
+    /// How the ship moves, unless told otherwise.
+    pub fn maneuver(self) -> Maneuver {
+        match self {
+            EnemyType::Weaver => Maneuver::Weave,
+            EnemyType::Diver => Maneuver::Dive,
+            EnemyType::Strafer => Maneuver::Strafe,
+            EnemyType::Hunter => Maneuver::Home,
+        }
+    }
+
+    /// The time between two shots, in seconds, if the ship has a cannon.
+    fn reload_time(self) -> Option<f64> {
+        match self {
+            EnemyType::Weaver => Some(1.6),
+            EnemyType::Strafer => Some(0.9),
+            EnemyType::Diver | EnemyType::Hunter => None,
+        }
+    }
+}
+
+/// The ways in which an enemy may move, those of the types of ships by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Maneuver {
+    Weave,
+    Dive,
+    Strafe,
+    Home,
+}
+
+/// Where an enemy is in its movement pattern.
+#[derive(Clone, Copy)]
+enum Pattern {
+    Weave { origin_y: f64 },
+    Dive { heading: Option<Vec2> },
+    Strafe { down: bool },
+    Home,
+}
+
+/// An enemy ship of the world.
+#[derive(Clone, Copy)]
+pub struct Enemy {
+    pub enemy_type: EnemyType,
+    pattern: Pattern,
+
+    /// How fast the enemy and its bullets move, relative to their usual speed.
+    pace: f64,
+
+    /// How long the enemy has been flying, in seconds.
+    time: f64,
+
+    /// The time left, in seconds, before it may shoot again.
+    reload: f64,
+}
+
+impl Enemy {
+    /// An enemy of the given type, which just appeared with its center at
+    /// `center_y`, and flies `maneuver` at `pace` times its usual speed.
+    pub fn new<R: Rng>(enemy_type: EnemyType, maneuver: Maneuver, center_y: f64, pace: f64, rng: &mut R) -> Enemy {
+        let pattern = match maneuver {
+            Maneuver::Weave => Pattern::Weave { origin_y: center_y },
+            Maneuver::Dive => Pattern::Dive { heading: None },
+            Maneuver::Strafe => Pattern::Strafe { down: rng.gen() },
+            Maneuver::Home => Pattern::Home,
+        };
+
+        Enemy {
+            enemy_type,
+            pattern,
+            pace,
+            time: 0.0,
+            reload: enemy_type.reload_time().unwrap_or(0.0) / 2.0,
+        }
+    }
+
+    /// Choose the velocity of the enemy, which is at `pos`, its center, and
+    /// currently moves at `vel`, towards `player`, the center of the player's
+    /// ship, for the next `dt` seconds.
+    fn steer(&mut self, pos: Vec2, vel: Vec2, player: Vec2, bounds: Rectangle, dt: f64) -> Vec2 {
+        let speed = self.enemy_type.speed() * self.pace;
+        let dive_speed = DIVE_SPEED * self.pace;
+        let ahead = Vec2::new(-speed, 0.0);
+        self.time += dt;
+
+        match self.pattern {
+            Pattern::Weave { origin_y } => {
+                // Head for where the sine wave is at the end of the frame.
+                let target_y = origin_y + WEAVE_AMPLITUDE * (WEAVE_ANGULAR_VEL * self.time).sin();
+                Vec2::new(-speed, if dt == 0.0 { 0.0 } else { (target_y - pos.y) / dt })
+            },
+
+            Pattern::Dive { heading: Some(heading) } =>
+                heading * dive_speed,
+
+            Pattern::Dive { heading: None } => {
+                if pos.x > player.x && pos.x - player.x < DIVE_RANGE {
+                    let heading = (player - pos).normalize();
+                    self.pattern = Pattern::Dive { heading: Some(heading) };
+                    heading * dive_speed
+                } else {
+                    ahead
+                }
+            },
+
+            Pattern::Strafe { down } => {
+                let hover_x = bounds.x + bounds.w * STRAFE_X;
+                if self.time > STRAFE_TIME || pos.x > hover_x {
+                    ahead
+                } else {
+                    // Turn back at the edges of the screen.
+                    let down =
+                        if pos.y < bounds.y + bounds.h * 0.1 { true }
+                        else if pos.y > bounds.y + bounds.h * 0.9 { false }
+                        else { down };
+                    self.pattern = Pattern::Strafe { down };
+                    Vec2::new(0.0, if down { speed } else { -speed })
+                }
+            },
+
+            Pattern::Home => {
+                if self.time > HOMING_TIME {
+                    return vel;
+                }
+
+                let towards_player = (player - pos).normalize() * speed;
+                let turned = vel.lerp(towards_player, (HOMING_TURN * dt).min(1.0));
+                if turned == Vec2::default() { towards_player } else { turned.normalize() * speed }
+            },
+        }
+    }
+
+    /// Whether the enemy shoots during this frame, lasting `dt` seconds.
+    fn shoots(&mut self, dt: f64) -> bool {
+        let reload_time = match self.enemy_type.reload_time() {
+            Some(reload_time) => reload_time,
+            None => return false,
+        };
+
+        self.reload -= dt;
+        if self.reload > 0.0 {
+            return false;
+        }
+
+        self.reload += reload_time;
+        true
+    }
+}
+
+/// The hitbox of an enemy ship whose sprite is `w` by `h` pixels, relative to
+/// the sprite.
+pub fn enemy_hitbox(w: f64, h: f64) -> Shape {
+    Shape::Polygon(Polygon {
+        points: ENEMY_HITBOX.iter()
+            .map(|&(x, y)| Vec2::new(x * w, y * h))
+            .collect(),
+    })
+}
+
+/// Move the enemies of the `world` along their pattern, around the player
+/// whose ship is centered on `player`, and make them shoot while they are in
+/// `bounds`.
+pub fn steer_enemies(world: &mut World<Kind>, player: Vec2, bounds: Rectangle, dt: f64) {
+    let mut shots = vec![];
+
+    for (entity, kind) in world.kinds.iter_mut() {
+        let enemy = match *kind {
+            Kind::Enemy(ref mut enemy) => enemy,
+            _ => continue,
+        };
+
+        let (transform, velocity) = match (world.transforms.get(entity), world.velocities.get_mut(entity)) {
+            (Some(transform), Some(velocity)) => (transform, velocity),
+            _ => continue,
+        };
+
+        let pos = transform.rect.center();
+        velocity.vel = enemy.steer(pos, velocity.vel, player, bounds, dt);
+
+        if enemy.shoots(dt) && bounds.contains_point(pos) {
+            let direction = match enemy.enemy_type {
+                EnemyType::Strafer => player - pos,
+                _ => Vec2::new(-1.0, 0.0),
+            };
+            shots.push((entity, Vec2::new(transform.rect.x, pos.y), direction, enemy.pace));
+        }
+    }
+
+    for (shooter, pos, direction, pace) in shots {
+        spawn_enemy_bullet(world, shooter, pos, direction, pace);
+    }
+}
+
+pub struct EnemyFactory {
+    animator: Animator,
+}
+
+impl EnemyFactory {
+    pub fn new(phi: &mut Phi) -> EnemyFactory {
+        let atlas = Atlas::load(&phi.renderer, ENEMY_PATH).unwrap();
+
+        EnemyFactory {
+            animator: atlas.animator("mid_norm").unwrap(),
+        }
+    }
+
+    /// Add an enemy of the given type to the `world`, coming from the right of
+    /// the screen and flying the given `maneuver` at `pace` times its usual
+    /// speed. Its `height` goes from 0, at the top of the screen, to 1, at the
+    /// bottom. If `None`, it is random.
+    pub fn spawn(&self, phi: &mut Phi, world: &mut World<Kind>, enemy_type: EnemyType, maneuver: Maneuver, height: Option<f64>, pace: f64) {
+        let (w, h) = phi.output_size();
+        let (sprite_w, sprite_h) = self.animator.size();
+
+        // Weavers must stay in the screen while going up and down.
+        let margin = match maneuver {
+            Maneuver::Weave => WEAVE_AMPLITUDE,
+            _ => 0.0,
+        };
+        let height = height.unwrap_or_else(|| phi.rng.gen());
+        let y = margin + height * (h - sprite_h - margin * 2.0);
+
+        let enemy = world.spawn(Kind::Enemy(Enemy::new(enemy_type, maneuver, y + sprite_h / 2.0, pace, &mut phi.rng)));
+        world.animators.insert(enemy, self.animator.clone());
+        world.transforms.insert(enemy, Transform {
+            rect: Rectangle { x: w, y, w: sprite_w, h: sprite_h },
+            angle: 0.0,
+        });
+        world.velocities.insert(enemy, Velocity {
+            vel: Vec2::new(-enemy_type.speed() * pace, 0.0),
+            spin: 0.0,
+        });
+        world.colliders.insert(enemy, Collider {
+            shape: enemy_hitbox(sprite_w, sprite_h),
+            swept: false,
+        });
+        world.healths.insert(enemy, Health { points: enemy_type.health() });
+    }
+}
@@ -0,0 +1,155 @@
+use crate::phi::ecs::Entity;
+use crate::views::bosses::{Boss, BossPart, Wreck};
+use crate::views::bullets::{Bullet, Faction};
+use crate::views::enemies::Enemy;
+
+/// What an entity of the game's world represents.
+pub enum Kind {
+    /// The player's ship, which is moved by `Player`.
+    Player,
+    Asteroid,
+    Enemy(Enemy),
+    Boss(Boss),
+    BossPart(BossPart),
+    Wreck(Wreck),
+    Explosion,
+    Bullet(Bullet),
+}
+
+impl Kind {
+    /// Whether the entity was sent by the stage at the player, which must get
+    /// rid of all of them to clear it.
+    pub fn is_hostile(&self) -> bool {
+        matches!(*self, Kind::Asteroid | Kind::Enemy(_) | Kind::Boss(_) | Kind::BossPart(_) | Kind::Wreck(_))
+    }
+
+    /// The side of a ship, if the entity is one.
+    fn faction(&self) -> Option<Faction> {
+        match *self {
+            Kind::Player => Some(Faction::Player),
+            Kind::Enemy(_) | Kind::Boss(_) | Kind::BossPart(_) => Some(Faction::Enemy),
+            _ => None,
+        }
+    }
+}
+
+/// Which entities of the world collide, and which of them gets hurt.
+pub struct CollisionMatrix {
+    /// Whether bullets hit the ships of the side which shot them, other than
+    /// their shooter, e.g. the other player in co-op.
+    pub friendly_fire: bool,
+}
+
+impl CollisionMatrix {
+    /// Whether `a` is destroyed, or damages `b`, when they collide. Whatever is
+    /// not listed goes through each other.
+    pub fn hits(&self, a: &Kind, b: &Kind) -> bool {
+        match (a, b) {
+            (Kind::Bullet(bullet), target) => match target.faction() {
+                Some(faction) => faction != bullet.owner || self.friendly_fire,
+                // Only the player shoots asteroids down.
+                None => matches!((bullet.owner, target), (Faction::Player, Kind::Asteroid)),
+            },
+
+            // The player's ship is rammed by anything solid.
+            (Kind::Asteroid | Kind::Enemy(_) | Kind::Boss(_) | Kind::BossPart(_), Kind::Player) => true,
+
+            _ => false,
+        }
+    }
+
+    /// Which of the entities `a` and `b`, of the given kinds, hits the other
+    /// when they collide, as `(attacker, target)`, if either does. Bullets
+    /// leave the ship which shot them unharmed.
+    pub fn resolve(&self, (a, kind_a): (Entity, &Kind), (b, kind_b): (Entity, &Kind)) -> Option<(Entity, Entity)> {
+        let hits = |attacker: &Kind, target: Entity, target_kind: &Kind| {
+            self.hits(attacker, target_kind) &&
+            !matches!(attacker, Kind::Bullet(bullet) if bullet.shooter == target)
+        };
+
+        if hits(kind_a, b, kind_b) { Some((a, b)) }
+        else if hits(kind_b, a, kind_a) { Some((b, a)) }
+        else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phi::ecs::World;
+    use crate::views::bullets::BulletPath;
+    use crate::views::enemies::{EnemyType, Maneuver};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const MATRIX: CollisionMatrix = CollisionMatrix { friendly_fire: false };
+    const FRIENDLY_FIRE: CollisionMatrix = CollisionMatrix { friendly_fire: true };
+
+    fn enemy() -> Kind {
+        Kind::Enemy(Enemy::new(EnemyType::Weaver, Maneuver::Weave, 0.0, 1.0, &mut StdRng::seed_from_u64(0)))
+    }
+
+    fn bullet(owner: Faction, shooter: Entity) -> Kind {
+        Kind::Bullet(Bullet { path: BulletPath::Straight, owner, shooter })
+    }
+
+    #[test]
+    fn player_bullets_hit_enemies_and_asteroids() {
+        let mut world = World::new();
+        let shooter = world.spawn(());
+        let shot = bullet(Faction::Player, shooter);
+
+        assert!(MATRIX.hits(&shot, &enemy()));
+        assert!(MATRIX.hits(&shot, &Kind::Asteroid));
+        assert!(!MATRIX.hits(&shot, &Kind::Player));
+        assert!(!MATRIX.hits(&shot, &Kind::Explosion));
+        assert!(FRIENDLY_FIRE.hits(&shot, &Kind::Player));
+    }
+
+    #[test]
+    fn enemy_bullets_hit_enemies_only_with_friendly_fire() {
+        let mut world = World::new();
+        let shooter = world.spawn(());
+        let shot = bullet(Faction::Enemy, shooter);
+
+        assert!(MATRIX.hits(&shot, &Kind::Player));
+        assert!(!MATRIX.hits(&shot, &enemy()));
+        assert!(FRIENDLY_FIRE.hits(&shot, &enemy()));
+        assert!(!MATRIX.hits(&shot, &Kind::Asteroid));
+        assert!(!FRIENDLY_FIRE.hits(&shot, &Kind::Asteroid));
+    }
+
+    #[test]
+    fn solid_things_ram_the_player() {
+        assert!(MATRIX.hits(&Kind::Asteroid, &Kind::Player));
+        assert!(MATRIX.hits(&enemy(), &Kind::Player));
+        assert!(!MATRIX.hits(&Kind::Player, &Kind::Asteroid));
+        assert!(!MATRIX.hits(&Kind::Player, &enemy()));
+        assert!(!MATRIX.hits(&Kind::Explosion, &Kind::Player));
+        assert!(!MATRIX.hits(&Kind::Asteroid, &enemy()));
+    }
+
+    #[test]
+    fn resolve_orders_attacker_and_target() {
+        let mut world = World::new();
+        let player = world.spawn(());
+        let asteroid = world.spawn(());
+
+        assert_eq!(MATRIX.resolve((player, &Kind::Player), (asteroid, &Kind::Asteroid)), Some((asteroid, player)));
+        assert_eq!(MATRIX.resolve((asteroid, &Kind::Asteroid), (player, &Kind::Player)), Some((asteroid, player)));
+        assert_eq!(MATRIX.resolve((player, &Kind::Player), (asteroid, &Kind::Explosion)), None);
+    }
+
+    #[test]
+    fn bullets_spare_their_shooter() {
+        let mut world = World::new();
+        let shooter = world.spawn(());
+        let other = world.spawn(());
+        let shot = world.spawn(());
+        let kind = bullet(Faction::Enemy, shooter);
+
+        assert_eq!(FRIENDLY_FIRE.resolve((shot, &kind), (shooter, &enemy())), None);
+        assert_eq!(FRIENDLY_FIRE.resolve((shooter, &enemy()), (shot, &kind)), None);
+        assert_eq!(FRIENDLY_FIRE.resolve((shot, &kind), (other, &enemy())), Some((shot, other)));
+    }
+}
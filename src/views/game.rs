@@ -1,233 +1,219 @@
 use crate::phi::{Phi, View, ViewAction};
-use crate::phi::data::{Rectangle, MaybeAlive};
-use crate::phi::gfx::{Sprite, CopySprite, AnimatedSprite, AnimatedSpriteDescr};
-use crate::views::shared::Background;
+use crate::phi::draw::{DrawList, Layer};
+use crate::phi::data::{Rectangle, Circle, Polygon, Shape, Vec2};
+use crate::phi::ecs::{Collider, Entity, Fate, Health, Transform, Velocity, World};
+use crate::phi::atlas::Atlas;
+use crate::phi::gfx::{CopySprite, AnimatedSprite, Animator, RenderOptions, BitmapFont};
+use crate::phi::particles::{Emitter, EmitterDescr};
+use crate::phi::text::{Align, TextStyle};
+use crate::views::bosses::{BossFactory, BossPart, Phase, Wreck, BOSS_POINTS, TURRET_POINTS, chain_explosions, steer_bosses};
+use crate::views::difficulty::Difficulty;
+use crate::views::entities::{CollisionMatrix, Kind};
+use crate::views::enemies::{EnemyFactory, EnemyType, steer_enemies};
+use crate::views::main_menu::MainMenuView;
+use crate::views::shared::*;
+use crate::views::stages::{SpawnDescr, Spawnable, StageRunner};
 use crate::views::bullets::*;
+use rand::Rng;
 use sdl2::pixels::Color;
 use sdl2::mixer::Music;
+use std::collections::VecDeque;
 use std::path::Path;
 
 
-const ASTEROID_PATH: &'static str = "assets/asteroid.png";
-const ASTEROID_WIDE: usize = 21;
-const ASTEROID_HIGH: usize = 7;
-const ASTEROID_TOTAL: usize = ASTEROID_WIDE * ASTEROID_HIGH - 4;
-const ASTEROID_SIDE: f64 = 96.0;
+const ASTEROID_PATH: &'static str = "assets/asteroid.ron";
+/// The fastest an asteroid may spin, in degrees per second.
+const ASTEROID_MAX_SPIN: f64 = 90.0;
+/// The range of the speed of the asteroids, in pixels per second, before the
+/// difficulty is applied.
+const ASTEROID_SPEED: (f64, f64) = (50.0, 150.0);
 
 // Constants about the explosion
-const EXPLOSION_PATH: &'static str = "assets/explosion.png";
-const EXPLOSIONS_WIDE: usize = 5;
-const EXPLOSIONS_HIGH: usize = 4;
-const EXPLOSIONS_TOTAL: usize = 17;
-const EXPLOSION_SIDE: f64 = 96.0;
-const EXPLOSION_FPS: f64 = 16.0;
-const EXPLOSION_DURATION: f64 = 1.0 / EXPLOSION_FPS * EXPLOSIONS_TOTAL as f64;
+const EXPLOSION_PATH: &'static str = "assets/explosion.ron";
 
 /// Pixels traveled by the player's ship every second, when it is moving
 const PLAYER_SPEED:f64 = 180.0;
-const PLAYER_PATH: &'static str = "assets/spaceship.png";
+/// The radius of an asteroid's hitbox, relative to the width of its sprite,
+/// whose corners are transparent.
+const ASTEROID_HITBOX_RADIUS: f64 = 0.4;
+
+/// The size of the cells of the grid in which bullets are sorted before
+/// testing collisions, about that of an asteroid.
+const COLLISION_CELL_SIZE: f64 = 96.0;
+
+/// The outline of the ship's hitbox, relative to its sprite, from the top of
+/// its tail, around its nose, to the bottom of its tail.
+const PLAYER_HITBOX: [(f64, f64); 5] = [
+    (0.05, 0.05), (0.6, 0.25), (1.0, 0.5), (0.6, 0.75), (0.05, 0.95),
+];
+
+const PLAYER_PATH: &'static str = "assets/spaceship.ron";
 
 /// BGM path
 const MUSIC_PATH: &'static str = "assets/mdk_phoenix_orchestral.ogg";
 
-// Constants about the ship
-const PLAYER_W: f64 = 43.0;
-const PLAYER_H: f64 = 39.0;
-
-const DEBUG: bool = false;
-
-/// The different states our ship might be in. In the image, they're ordered
-/// from left to right, then top to bottom.
-#[derive(Clone, Copy)]
-enum PlayerFrame {
-    UpNorm   = 0,
-    UpFast   = 1,
-    UpSlow   = 2,
-    MidNorm  = 3,
-    MidFast  = 4,
-    MidSlow  = 5,
-    DownNorm = 6,
-    DownFast = 7,
-    DownSlow = 8
-}
+const FONT_PATH: &str = "assets/belligerent.ttf";
+const HUD_FONT_PATH: &str = "assets/pixel_font.fnt";
 
-#[derive(Clone)]
-struct Asteroid {
-    sprite: AnimatedSprite,
-    rect: Rectangle,
-    vel: f64,
-}
+/// How many screen pixels a pixel of the HUD's font takes, in each direction.
+const HUD_SCALE: u32 = 3;
 
-impl Asteroid {
-    fn factory(phi: &mut Phi) -> AsteroidFactory {
-        AsteroidFactory {
-            sprite: AnimatedSprite::with_fps(
-                AnimatedSprite::load_frames(phi, AnimatedSpriteDescr {
-                    image_path: ASTEROID_PATH,
-                    total_frames: ASTEROID_TOTAL,
-                    frames_high: ASTEROID_HIGH,
-                    frames_wide: ASTEROID_WIDE,
-                    frame_w: ASTEROID_SIDE,
-                    frame_h: ASTEROID_SIDE,
-                }), 1.0),
-        }
-    }
+/// The number of sparks emitted when a bullet hits an asteroid.
+const IMPACT_SPARKS: usize = 8;
 
-    fn update(mut self, dt: f64) -> Option<Asteroid>{
-        self.rect.x -= dt * self.vel;
-        self.sprite.add_time(dt);
+/// The number of fragments emitted when an asteroid is destroyed.
+const ASTEROID_DEBRIS: usize = 24;
 
-        if self.rect.x <= -ASTEROID_SIDE {
-            None
-        } else {
-            Some(self)
-        }
-    }
+/// How far, in pixels, the screen shakes when an asteroid explodes, and when
+/// the player is hit.
+const EXPLOSION_SHAKE: f64 = 3.0;
+const PLAYER_HIT_SHAKE: f64 = 12.0;
 
-    fn render(&self, phi: &mut Phi) {
-        if DEBUG {
-            // Render the bounding box.
-            phi.renderer.set_draw_color(Color::RGB(200, 200, 50));
-            phi.renderer.fill_rect(self.rect().to_sdl()).unwrap();
-        }
+/// How far the screen shakes when the wreck of a boss blows up, and how many
+/// fragments it scatters.
+const BOSS_DEATH_SHAKE: f64 = 20.0;
+const BOSS_DEBRIS: usize = 96;
 
-        phi.renderer.copy_sprite(&self.sprite, self.rect);
-    }
+/// The stages of the game, played in this order.
+const STAGE_PATHS: [&str; 2] = [
+    "assets/stages/stage1.ron",
+    "assets/stages/stage2.ron",
+];
 
-    fn rect(&self) -> Rectangle {
-        self.rect
-    }
-}
+/// How long the name of a stage, or the news that it was cleared, stays on
+/// the screen, in seconds.
+const BANNER_TIME: f64 = 3.0;
+
+/// The height of the health bar of a boss, in pixels, and its width, relative
+/// to that of the screen.
+const BOSS_BAR_H: f64 = 12.0;
+const BOSS_BAR_W: f64 = 0.5;
+
+/// How long the ship flashes red after being hit, in seconds.
+const PLAYER_HIT_FLASH: f64 = 0.3;
 
 struct AsteroidFactory {
     sprite: AnimatedSprite,
 }
 
 impl AsteroidFactory {
-    fn random(&self, phi: &mut Phi) -> Asteroid {
+    fn new(phi: &mut Phi) -> AsteroidFactory {
+        let atlas = Atlas::load(&phi.renderer, ASTEROID_PATH).unwrap();
+
+        AsteroidFactory {
+            sprite: atlas.animated("spin").unwrap(),
+        }
+    }
+
+    /// Add an asteroid to the `world`, coming from the right of the screen at
+    /// `pace` times its usual speed. Its `height` goes from 0, at the top of
+    /// the screen, to 1, at the bottom. If `None`, it is random.
+    fn spawn(&self, phi: &mut Phi, world: &mut World<Kind>, height: Option<f64>, pace: f64) {
         let (w, h) = phi.output_size();
+        let (sprite_w, sprite_h) = self.sprite.size();
 
         // FPS in [10.0, 30.0)
         let mut sprite = self.sprite.clone();
-        sprite.set_fps(::rand::random::<f64>().abs() * 20.0 + 10.0);
+        sprite.set_fps(phi.rng.gen::<f64>() * 20.0 + 10.0);
 
-        Asteroid {
-            sprite: sprite,
+        let asteroid = world.spawn(Kind::Asteroid);
+        world.animators.insert(asteroid, Animator::new("spin", sprite));
 
-            // In the screen vertically, and over the right of the screen
-            // horizontally
+        // In the screen vertically, and over the right of the screen
+        // horizontally
+        world.transforms.insert(asteroid, Transform {
             rect: Rectangle {
-                w: ASTEROID_SIDE,
-                h: ASTEROID_SIDE,
+                w: sprite_w,
+                h: sprite_h,
                 x: w,
-                y: ::rand::random::<f64>().abs() * (h - ASTEROID_SIDE),
+                y: height.unwrap_or_else(|| phi.rng.gen()) * (h - sprite_h),
             },
-            vel: ::rand::random::<f64>().abs() * 100.0 + 50.0,
-        }
+            angle: 0.0,
+        });
+        world.velocities.insert(asteroid, Velocity {
+            vel: Vec2::new(-phi.rng.gen_range(ASTEROID_SPEED.0..ASTEROID_SPEED.1) * pace, 0.0),
+            spin: (phi.rng.gen::<f64>() * 2.0 - 1.0) * ASTEROID_MAX_SPIN,
+        });
+        world.colliders.insert(asteroid, Collider {
+            shape: Shape::Circle(Circle {
+                center: Vec2::new(sprite_w / 2.0, sprite_h / 2.0),
+                radius: sprite_w * ASTEROID_HITBOX_RADIUS,
+            }),
+            swept: false,
+        });
+        world.healths.insert(asteroid, Health { points: 1 });
     }
 }
 
-struct Explosion {
-    sprite: AnimatedSprite,
-    rect: Rectangle,
-
-    // Keep how long its been arived, so that we destroy the explosion once
-    // its animation is finished.
-    alive_since: f64,
+struct ExplosionFactory {
+    animator: Animator,
 }
 
-impl Explosion {
-    fn factory(phi: &mut Phi) -> ExplosionFactory {
+impl ExplosionFactory {
+    fn new(phi: &mut Phi) -> ExplosionFactory {
+        let atlas = Atlas::load(&phi.renderer, EXPLOSION_PATH).unwrap();
+
         ExplosionFactory {
-            sprite: AnimatedSprite::with_fps(
-                AnimatedSprite::load_frames(phi, AnimatedSpriteDescr {
-                    image_path: EXPLOSION_PATH,
-                    total_frames: EXPLOSIONS_TOTAL,
-                    frames_high: EXPLOSIONS_HIGH,
-                    frames_wide: EXPLOSIONS_WIDE,
-                    frame_w: EXPLOSION_SIDE,
-                    frame_h: EXPLOSION_SIDE,
-                }), EXPLOSION_FPS),
+            animator: atlas.animator("explode").unwrap(),
         }
     }
 
-    fn update(mut self, dt: f64) -> Option<Explosion> {
-        self.alive_since += dt;
-        self.sprite.add_time(dt);
-
-        if self.alive_since >= EXPLOSION_DURATION {
-            None
-        } else {
-            Some(self)
-        }
+    /// Add to the `world` an explosion centered on `center`. It lasts until
+    /// its animation is over.
+    fn spawn_at(&self, world: &mut World<Kind>, center: Vec2) {
+        let (w, h) = self.animator.size();
+        self.spawn(world, Rectangle::with_size(w, h).center_at(center));
     }
 
-    fn render(&self, phi: &mut Phi) {
-        phi.renderer.copy_sprite(&self.sprite, self.rect);
+    /// Add to the `world` an explosion scaled to cover `rect`, e.g. one of the
+    /// blasts going off on a wreck.
+    fn spawn_covering(&self, world: &mut World<Kind>, rect: Rectangle) {
+        let (w, h) = self.animator.size();
+        self.spawn(world, Rectangle::with_size(w, h)
+            .center_at(rect.center())
+            .scale((rect.w / w).max(rect.h / h)));
     }
-}
 
-struct ExplosionFactory {
-    sprite: AnimatedSprite,
-}
-
-impl ExplosionFactory {
-    fn at_center(&self, center: (f64, f64)) -> Explosion {
-        // FPS in [10.0, 30.0)
-        let sprite = self.sprite.clone();
-
-        Explosion {
-            sprite: sprite,
-
-            // In the screen vertically, and over the right of the screen
-            // horizontally
-            rect: Rectangle::with_size(EXPLOSION_SIDE, EXPLOSION_SIDE).center_at(center),
-
-            alive_since: 0.0,
-        }
+    fn spawn(&self, world: &mut World<Kind>, rect: Rectangle) {
+        let explosion = world.spawn(Kind::Explosion);
+        world.transforms.insert(explosion, Transform { rect, angle: 0.0 });
+        world.animators.insert(explosion, self.animator.clone());
     }
 }
 
 #[derive(Clone)]
 struct Player {
     rect: Rectangle,
-    sprites: Vec<Sprite>,
-    current: PlayerFrame,
+    animator: Animator,
     cannon: CannonType,
+    /// Time left, in seconds, during which the ship flashes after a hit.
+    hit_flash: f64,
 }
 
 impl Player {
     pub fn new(phi: &mut Phi) -> Player {
-        // Get the spaceship's sprites.
-        let spritesheet = Sprite::load(&mut phi.renderer, PLAYER_PATH).unwrap();
-        let mut sprites = Vec::with_capacity(9);
-
-        for y in 0..3 {
-            for x in 0..3 {
-                sprites.push(spritesheet.region(Rectangle {
-                    w: PLAYER_W,
-                    h: PLAYER_H,
-                    x: PLAYER_W * x as f64,
-                    y: PLAYER_H * y as f64,
-                }).unwrap());
-            }
-        }
+        // Get the spaceship's animations.
+        let atlas = Atlas::load(&phi.renderer, PLAYER_PATH).unwrap();
+        let animator = atlas.animator("mid_norm").unwrap();
+        let (w, h) = animator.size();
 
         Player {
             // Spawn the player at the center of the screen, vertically.
             rect: Rectangle {
                 x: 64.0,
-                y: (phi.output_size().1 - PLAYER_H) / 2.0,
-                w: PLAYER_W,
-                h: PLAYER_H,
+                y: (phi.output_size().1 - h) / 2.0,
+                w,
+                h,
             },
-            sprites: sprites,
-            current: PlayerFrame::MidNorm,
+            animator,
             cannon: CannonType::RectBullet,
+            hit_flash: 0.0,
         }
     }
 
     pub fn update(&mut self, phi: &mut Phi, elapsed: f64) {
+        self.hit_flash = (self.hit_flash - elapsed).max(0.0);
+
         // Change the player's cannons
         if phi.events.now.key_1 == Some(true) {
             self.cannon = CannonType::RectBullet;
@@ -247,29 +233,22 @@ impl Player {
             }
         }
 
-        // Moving logic
-        let diagonal = 
-            (phi.events.key_up ^ phi.events.key_down) &&
-            (phi.events.key_left ^ phi.events.key_right);
-
-        let moved = 
-            if diagonal { 1.0 / 2.0f64.sqrt()}
-            else { 1.0 } * PLAYER_SPEED * elapsed;
-        
-        let dx = match (phi.events.key_left, phi.events.key_right) {
-            (true, true) | (false, false) => 0.0,
-            (true, false) => -moved,
-            (false, true) => moved,
-        };
-
-        let dy = match (phi.events.key_up, phi.events.key_down) {
-            (true, true) | (false, false) => 0.0,
-            (true, false) => -moved,
-            (false, true) => moved,
-        };
+        // Moving logic, at the same speed in every direction, diagonals
+        // included.
+        let direction = Vec2::new(
+            match (phi.events.key_left, phi.events.key_right) {
+                (true, true) | (false, false) => 0.0,
+                (true, false) => -1.0,
+                (false, true) => 1.0,
+            },
+            match (phi.events.key_up, phi.events.key_down) {
+                (true, true) | (false, false) => 0.0,
+                (true, false) => -1.0,
+                (false, true) => 1.0,
+            });
 
-        self.rect.x += dx;
-        self.rect.y += dy;
+        let moved = direction.normalize() * PLAYER_SPEED * elapsed;
+        self.rect = self.rect.translate(moved);
 
         // The movable region spans the entire height of the window and 70% of its
         // width. This way, the player cannot get to the far right of the screen, where
@@ -287,93 +266,273 @@ impl Player {
         // the game should be promptly aborted.
         self.rect = self.rect.move_inside(movable_region).unwrap();
 
-        // Select the appropriate sprite of the ship to show.
-        self.current = 
-            if dx == 0.0 && dy < 0.0       { PlayerFrame::UpNorm }
-            else if dx > 0.0 && dy < 0.0   { PlayerFrame::UpFast }
-            else if dx < 0.0 && dy < 0.0   { PlayerFrame::UpSlow }
-            else if dx == 0.0 && dy == 0.0 { PlayerFrame::MidNorm }
-            else if dx > 0.0 && dy == 0.0  { PlayerFrame::MidFast }
-            else if dx < 0.0 && dy == 0.0  { PlayerFrame::MidSlow }
-            else if dx == 0.0 && dy > 0.0  { PlayerFrame::DownNorm }
-            else if dx > 0.0 && dy > 0.0   { PlayerFrame::DownFast }
-            else if dx < 0.0 && dy > 0.0   { PlayerFrame::DownSlow }
-            else { unreachable!() };
+        // Select the clip of the ship matching its direction. Its name is
+        // given by the atlas, e.g. `up_fast` when moving up and to the right.
+        let row =
+            if moved.y < 0.0 { "up" }
+            else if moved.y > 0.0 { "down" }
+            else { "mid" };
+
+        let col =
+            if moved.x > 0.0 { "fast" }
+            else if moved.x < 0.0 { "slow" }
+            else { "norm" };
+
+        self.animator.play(&format!("{}_{}", row, col));
+        self.animator.add_time(elapsed);
     }
 
-    pub fn render(&self, phi: &mut Phi) {
-        // Render the bounding box(for debugging purposes)
-        if DEBUG {
-            phi.renderer.set_draw_color(Color::RGB(200, 200, 50));
-            phi.renderer.fill_rect(self.rect.to_sdl()).unwrap();
+    fn collider(&self) -> Collider {
+        Collider {
+            shape: Shape::Polygon(Polygon {
+                points: PLAYER_HITBOX.iter()
+                    .map(|&(x, y)| Vec2::new(x * self.rect.w, y * self.rect.h))
+                    .collect(),
+            }),
+            swept: false,
         }
+    }
 
-        // Render the ship's current sprite.
-        phi.renderer.copy_sprite (
-            &self.sprites[self.current as usize],
-            self.rect
+    pub fn render<'a>(&'a self, list: &mut DrawList<'a>) {
+        // Render the ship's current sprite, in red if it was recently hit.
+        let tint =
+            if self.hit_flash > 0.0 { Color::RGB(255, 60, 60) }
+            else { Color::RGB(255, 255, 255) };
+
+        list.renderable_ex(
+            Layer::Scene,
+            Z_PLAYER,
+            &self.animator,
+            self.rect,
+            RenderOptions { tint, ..Default::default() }
         );
     }
 
-    pub fn spawn_bullets(&self) -> Vec<Box<dyn Bullet>> {
+    /// Make the ship flash to signal that it was hit.
+    pub fn hit(&mut self) {
+        self.hit_flash = PLAYER_HIT_FLASH;
+    }
+
+    /// Shoot from the ship, which is the `entity` of the world.
+    pub fn spawn_bullets(&self, world: &mut World<Kind>, entity: Entity) {
         let cannons_x = self.rect.x + 30.0;
         let cannons1_y = self.rect.y + 6.0;
-        let cannons2_y = self.rect.y + PLAYER_H - 10.0;
+        let cannons2_y = self.rect.y + self.rect.h - 10.0;
 
-        spawn_bullets(self.cannon, cannons_x, cannons1_y, cannons2_y)
+        spawn_bullets(world, entity, self.cannon, cannons_x, cannons1_y, cannons2_y)
     }
 }
 
 pub struct GameView {
     player: Player,
-    bullets: Vec<Box<dyn Bullet>>,
-    asteroids: Vec<Asteroid>,
+
+    /// The player's ship, bullets, asteroids, enemies and explosions.
+    world: World<Kind>,
+
+    /// The entity of the player's ship, which follows `player`.
+    player_entity: Entity,
     asteroid_factory: AsteroidFactory,
-    explosions: Vec<Explosion>,
+    enemy_factory: EnemyFactory,
+    boss_factory: BossFactory,
     explosion_factory: ExplosionFactory,
+    collision_matrix: CollisionMatrix,
+    difficulty: Difficulty,
     music: Music<'static>,
 
-    bg_back: Background,
-    bg_middle: Background,
-    bg_front: Background,
+    /// The stage being played, and those which follow it, all loaded when
+    /// the game starts so that a broken stage file is reported right away.
+    stage: StageRunner,
+    next_stages: VecDeque<StageRunner>,
+    stage_cleared: bool,
+
+    /// The message shown in the middle of the screen, and for how long still,
+    /// in seconds.
+    banner: Option<(String, f64)>,
+
+    /// How long the current game has lasted, in seconds.
+    play_time: f64,
+
+    /// The points earned by shooting down asteroids and enemies.
+    score: u32,
+
+    /// Whether everything flies through the player, set from the console.
+    god: bool,
+    hud_font: BitmapFont,
+
+    // Particles
+    exhaust: Emitter,
+    sparks: Emitter,
+    debris: Emitter,
+
+    backgrounds: ParallaxStack,
 }
 
 impl GameView {
-    pub fn new(phi: &mut Phi) -> GameView {
+    /// Starts a new game at the given `difficulty`, in front of the given
+    /// `backgrounds`, which keep scrolling from where they were.
+    pub fn new(phi: &mut Phi, backgrounds: ParallaxStack, difficulty: Difficulty) -> GameView {
         let music = Music::from_file(Path::new(MUSIC_PATH)).unwrap();
         music.play(-1).unwrap();
         
+        let mut next_stages: VecDeque<StageRunner> = STAGE_PATHS.iter()
+            .map(|path| StageRunner::load(path).unwrap())
+            .collect();
+        let stage = next_stages.pop_front().unwrap();
+        let banner = Some((stage.name().to_string(), BANNER_TIME));
+
+        let player = Player::new(phi);
+        let mut world = World::new();
+        let player_entity = world.spawn(Kind::Player);
+        world.transforms.insert(player_entity, Transform { rect: player.rect, angle: 0.0 });
+        world.colliders.insert(player_entity, player.collider());
+
         GameView {
-            player: Player::new(phi),
-            /// We start with no bullets. Because the size of the vector will
-            /// change drastically throughout the program, there is not much
-            /// point in giving it a capacity.
-            bullets: vec![],
-            asteroids: vec![],
-            asteroid_factory: Asteroid::factory(phi),
-            explosions: vec![],
-            explosion_factory: Explosion::factory(phi),
+            player,
+            world,
+            player_entity,
+            asteroid_factory: AsteroidFactory::new(phi),
+            enemy_factory: EnemyFactory::new(phi),
+            boss_factory: BossFactory::new(phi),
+            explosion_factory: ExplosionFactory::new(phi),
+            collision_matrix: CollisionMatrix { friendly_fire: false },
+            difficulty: Difficulty::new(difficulty.preset, difficulty.adaptive),
+            stage,
+            next_stages,
+            stage_cleared: false,
+            banner,
             // Audio
             music: music,
+            play_time: 0.0,
+            score: 0,
+            god: false,
+            hud_font: BitmapFont::load(&phi.renderer, HUD_FONT_PATH).unwrap(),
+
+            exhaust: Emitter::new(EmitterDescr {
+                rate: 60.0,
+                lifetime: (0.15, 0.35),
+                speed: (80.0, 140.0),
+                direction: 180.0,
+                spread: 12.0,
+                gravity: Vec2::default(),
+                start_color: Color::RGBA(255, 220, 120, 255),
+                end_color: Color::RGBA(200, 40, 20, 0),
+                start_size: 5.0,
+                end_size: 1.0,
+                sprite: None,
+                max_particles: 64,
+            }),
+
+            sparks: Emitter::new(EmitterDescr {
+                rate: 0.0,
+                lifetime: (0.1, 0.3),
+                speed: (100.0, 250.0),
+                direction: 180.0,
+                spread: 70.0,
+                gravity: Vec2::default(),
+                start_color: Color::RGBA(255, 255, 150, 255),
+                end_color: Color::RGBA(230, 230, 30, 0),
+                start_size: 3.0,
+                end_size: 1.0,
+                sprite: None,
+                max_particles: 256,
+            }),
+
+            debris: Emitter::new(EmitterDescr {
+                rate: 0.0,
+                lifetime: (0.5, 1.2),
+                speed: (30.0, 160.0),
+                direction: 0.0,
+                spread: 180.0,
+                gravity: Vec2::new(-40.0, 0.0),
+                start_color: Color::RGBA(150, 130, 110, 255),
+                end_color: Color::RGBA(90, 80, 70, 0),
+                start_size: 6.0,
+                end_size: 2.0,
+                sprite: None,
+                max_particles: 512,
+            }),
+
+            backgrounds,
+        }
+    }
 
-            bg_back: Background {
-                pos: 0.0,
-                vel: 20.0,
-                sprite: Sprite::load(&mut phi.renderer, "assets/starBG.png").unwrap(),
-            },
+    fn set_cannon(&mut self, cannon: CannonType) -> Result<String, String> {
+        self.player.cannon = cannon;
+        Ok(format!("cannon set to {}", cannon.describe()))
+    }
 
-            bg_middle: Background {
-                pos: 0.0,
-                vel: 40.0,
-                sprite: Sprite::load(&mut phi.renderer, "assets/starMG.png").unwrap(),
-            },
+    /// Add to the world whatever the stage sends.
+    fn spawn(&mut self, phi: &mut Phi, spawn: SpawnDescr) {
+        let pace = self.difficulty.pace();
+        match spawn.what {
+            Spawnable::Asteroid =>
+                self.asteroid_factory.spawn(phi, &mut self.world, spawn.height, pace),
+
+            Spawnable::Enemy { ship, maneuver } =>
+                self.enemy_factory.spawn(phi, &mut self.world, ship, maneuver.unwrap_or(ship.maneuver()), spawn.height, pace),
 
-            bg_front: Background {
-                pos: 0.0,
-                vel: 80.0,
-                sprite: Sprite::load(&mut phi.renderer, "assets/starFG.png").unwrap(),
+            Spawnable::Boss =>
+                self.boss_factory.spawn(phi, &mut self.world, pace),
+        }
+    }
+
+    /// Whether the opaque pixels of the `asteroid` cover those of the ship.
+    fn hits_player(&self, asteroid: Entity) -> bool {
+        let (transform, animator) = match (self.world.transforms.get(asteroid), self.world.animators.get(asteroid)) {
+            (Some(transform), Some(animator)) => (transform, animator),
+            _ => return false,
+        };
+
+        transform.rect.overlaps(self.player.rect) &&
+        animator.clip().sprite().overlaps(
+            transform.rect, &RenderOptions { angle: transform.angle, ..Default::default() },
+            self.player.animator.clip().sprite(), self.player.rect, &RenderOptions::default())
+    }
+
+    /// Resolves `a` colliding with `b`, as allowed by the collision matrix.
+    /// Returns whether the player was hit.
+    fn collide(&mut self, phi: &mut Phi, a: Entity, b: Entity) -> bool {
+        if let Some(Kind::Player) = self.world.kinds.get(b) {
+            // Asteroids only hit where they are opaque.
+            if self.god || (matches!(self.world.kinds.get(a), Some(Kind::Asteroid)) && !self.hits_player(a)) {
+                return false;
             }
+
+            // Whatever hits the ship is destroyed, unless it is a boss.
+            if !matches!(self.world.kinds.get(a), Some(Kind::Boss(_) | Kind::BossPart(_))) {
+                if let Some(health) = self.world.healths.get_mut(a) {
+                    health.points = 0;
+                }
+            }
+            return true;
         }
+
+        // A bullet hits an asteroid or an enemy, which counts if shot down by
+        // the player. Bosses are unharmed while their turrets shield them.
+        let by_player = matches!(self.world.kinds.get(a), Some(Kind::Bullet(Bullet { owner: Faction::Player, .. })));
+        let shielded = matches!(self.world.kinds.get(b), Some(Kind::Boss(boss)) if boss.phase == Phase::Shielded);
+        if let Some(health) = self.world.healths.get_mut(b) {
+            if health.points > 0 && !shielded {
+                health.points -= 1;
+                if health.points == 0 && by_player {
+                    self.difficulty.scored();
+                    self.score += match self.world.kinds.get(b) {
+                        Some(Kind::Enemy(enemy)) => enemy.enemy_type.points(),
+                        Some(Kind::Boss(_)) => BOSS_POINTS,
+                        Some(Kind::BossPart(_)) => TURRET_POINTS,
+                        _ => 1,
+                    };
+                }
+            }
+        }
+        if let Some(health) = self.world.healths.get_mut(a) {
+            health.points = 0;
+        }
+        if let Some(transform) = self.world.transforms.get(a) {
+            self.sparks.burst(transform.rect.center(), IMPACT_SPARKS, &mut phi.rng);
+        }
+
+        false
     }
 }
 
@@ -383,6 +542,15 @@ impl View for GameView {
             return ViewAction::Quit;
         }
 
+        // Go back to the menu, with the backgrounds where they are.
+        if phi.events.now.key_escape == Some(true) {
+            let backgrounds = self.backgrounds;
+            return ViewAction::Render(Box::new(MainMenuView::with_backgrounds(phi, backgrounds, self.difficulty)));
+        }
+
+        // Whether the last stage was cleared.
+        let mut finished = false;
+
         {
             let game = &mut *self;
 
@@ -390,129 +558,383 @@ impl View for GameView {
 
             game.music.play(-1).unwrap();
 
-            // Update the bullets
-            game.bullets = 
-                ::std::mem::replace(&mut game.bullets, vec![])
-                .into_iter()
-                .filter_map(|bullet| bullet.update(phi, elapsed))
-                .collect();
-    
-            // Update the asteroids
-            game.asteroids =
-                ::std::mem::replace(&mut game.asteroids, vec![])
-                .into_iter()
-                .filter_map(|asteroid| asteroid.update(elapsed))
-                .collect();
-    
-            // Update the explosions
-            game.explosions =
-                ::std::mem::replace(&mut game.explosions, vec![])
-                .into_iter()
-                .filter_map(|explosion| explosion.update(elapsed))
-                .collect();
-            
+            // Move and animate everything else around the player.
+            let (w, h) = phi.output_size();
+            let bounds = Rectangle::with_size(w, h);
+            if let Some(transform) = game.world.transforms.get_mut(game.player_entity) {
+                transform.rect = game.player.rect;
+            }
+            steer_bullets(&mut game.world, elapsed);
+            steer_enemies(&mut game.world, game.player.rect.center(), bounds, elapsed);
+            steer_bosses(&mut game.world, game.player.rect.center(), elapsed);
+            game.world.movement(elapsed);
+            game.world.animation(elapsed);
+
             // Collision detection
-    
+            let matrix = &game.collision_matrix;
+            let collisions = game.world.collisions(elapsed, COLLISION_CELL_SIZE,
+                |a, b| matrix.hits(a, b) || matrix.hits(b, a));
+
             let mut player_alive = true;
-    
-            let mut transition_bullets: Vec<_> =
-                ::std::mem::replace(&mut game.bullets, vec![])
-                .into_iter()
-                .map(|bullet| MaybeAlive { alive: true, value: bullet })
-                .collect();
-    
-            game.asteroids =
-                ::std::mem::replace(&mut game.asteroids, vec![])
-                .into_iter()
-                .filter_map(|asteroid| {
-                    // By default, the asteroid has not been in a collision.
-                    let mut asteroid_alive = true;
-
-                    for bullet in &mut transition_bullets {
-                        if asteroid.rect().overlaps(bullet.value.rect()) {
-                            asteroid_alive = false;
-                            bullet.alive = false;
-                        }
-                    }
-
-                    // The player's ship is destroyed if it is hit by an asteroid.
-                    // In which case, the asteroid is also destroyed.
-                    if asteroid.rect().overlaps(game.player.rect) {
-                        asteroid_alive = false;
-                        player_alive = false;
-                    }
-
-                    if asteroid_alive {
-                        Some(asteroid)
-                    } else {
-                        // Spawn an explosive wherever an asteroid was destroyed.
-                        game.explosions.push(
-                            game.explosion_factory.at_center(
-                                asteroid.rect().center()));
-                        None
-                    }
-                })
-                .collect();
-    
-            game.bullets = transition_bullets.into_iter()
-                .filter_map(MaybeAlive::as_option)
-                .collect();
+            for (a, b) in collisions {
+                let pair = game.world.kinds.get(a).zip(game.world.kinds.get(b))
+                    .and_then(|(kind_a, kind_b)| game.collision_matrix.resolve((a, kind_a), (b, kind_b)));
+                let (attacker, target) = match pair {
+                    Some(pair) => pair,
+                    None => continue,
+                };
+
+                // Whatever was destroyed earlier during this frame, e.g. a
+                // bullet which already hit something, hits nothing else, and
+                // nothing hits it.
+                let dead = |entity| matches!(game.world.healths.get(entity), Some(health) if health.points == 0);
+                if dead(attacker) || dead(target) {
+                    continue;
+                }
+
+                player_alive &= !game.collide(phi, attacker, target);
+            }
+
+            // Remove what was destroyed, burnt out, or left the screen, and
+            // spawn an explosion wherever a ship or an asteroid was destroyed.
+            // Destroyed bosses leave a wreck, which burns before it blows up.
+            for (kind, transform, fate) in game.world.despawn_dead(elapsed, bounds) {
+                match (kind, fate) {
+                    (Kind::Asteroid | Kind::Enemy(_) | Kind::BossPart(_), Fate::Killed) => {
+                        game.explosion_factory.spawn_at(&mut game.world, transform.rect.center());
+                        game.debris.burst(transform.rect.center(), ASTEROID_DEBRIS, &mut phi.rng);
+                        phi.camera.shake(EXPLOSION_SHAKE);
+                    },
+
+                    (Kind::Boss(_), Fate::Killed) =>
+                        game.boss_factory.spawn_wreck(&mut game.world, transform),
+
+                    (Kind::Wreck(_), Fate::Expired) => {
+                        game.explosion_factory.spawn_covering(&mut game.world, transform.rect.scale(1.5));
+                        game.debris.burst(transform.rect.center(), BOSS_DEBRIS, &mut phi.rng);
+                        phi.camera.shake(BOSS_DEATH_SHAKE);
+                    },
+
+                    _ => {},
+                }
+            }
+
+            for blast in chain_explosions(&mut game.world, &mut phi.rng, elapsed) {
+                game.explosion_factory.spawn_covering(&mut game.world, blast);
+                phi.camera.shake(EXPLOSION_SHAKE);
+            }
 
             // TODO:
             // For the moment, we won'tdo anything about the player dying. This will be
             // the subject of a future episode.
             if !player_alive {
                 println!("The player's ship has been destroyed.");
+
+                // Contact which lasts several frames, e.g. with a boss, which
+                // is not destroyed by it, only counts once.
+                if game.player.hit_flash == 0.0 {
+                    game.difficulty.player_hit();
+                }
+                game.player.hit();
+                phi.camera.shake(PLAYER_HIT_SHAKE);
             }
     
             // Allow the player to shoot after the bullets are updated, so that,
             // when rendered for the first time, they are drawn wherever they
             // spawned.
             if phi.events.now.key_space == Some(true) {
-                game.bullets.append(&mut game.player.spawn_bullets());
+                game.player.spawn_bullets(&mut game.world, game.player_entity);
             }
     
-            // Randomly create an asteroid about once every 100 frames, that is,
-            // a bit more often than once every two seconds.
-            if ::rand::random::<usize>() % 100  == 0 {
-                game.asteroids.push(game.asteroid_factory.random(phi));
+            // Send whatever the stage calls for at this time. The stage is
+            // cleared once all of its waves were sent and got rid of.
+            let cleared = !game.world.kinds.iter().any(|(_, kind)| kind.is_hostile());
+            for spawn in game.stage.update(elapsed * game.difficulty.spawn_rate(), cleared) {
+                game.spawn(phi, spawn);
+            }
+
+            if game.stage.is_over() && cleared && !game.stage_cleared {
+                game.stage_cleared = true;
+                game.banner = Some(("STAGE CLEAR".to_string(), BANNER_TIME));
+            }
+
+            if let Some((_, ref mut remaining)) = game.banner {
+                *remaining -= elapsed;
+            }
+            if matches!(game.banner, Some((_, remaining)) if remaining <= 0.0) {
+                game.banner = None;
+            }
+
+            // Once the news is out, move on to the next stage, if any.
+            if game.stage_cleared && game.banner.is_none() {
+                match game.next_stages.pop_front() {
+                    Some(stage) => {
+                        game.stage = stage;
+                        game.stage_cleared = false;
+                        game.banner = Some((game.stage.name().to_string(), BANNER_TIME));
+                    },
+                    None => finished = true,
+                }
             }
     
+            game.play_time += elapsed;
+
+            // Update the particles, the exhaust following the back of the ship.
+            game.exhaust.active = true;
+            game.exhaust.pos = Vec2::new(game.player.rect.x + 4.0, game.player.rect.center().y);
+            game.exhaust.update(elapsed, &mut phi.rng);
+            game.sparks.update(elapsed, &mut phi.rng);
+            game.debris.update(elapsed, &mut phi.rng);
+
             // Update the backgrounds
-            game.bg_back.update(elapsed);
-            game.bg_middle.update(elapsed);
-            game.bg_front.update(elapsed);
+            game.backgrounds.update(elapsed);
         }
+
+        // Go back to the menu once the game is won.
+        if finished {
+            let backgrounds = self.backgrounds;
+            return ViewAction::Render(Box::new(MainMenuView::with_backgrounds(phi, backgrounds, self.difficulty)));
+        }
+
         // Update the player
         ViewAction::Render(self)
     }
 
+    fn command(&mut self, phi: &mut Phi, args: &[&str]) -> Result<String, String> {
+        let number = |arg: &str| arg.parse::<f64>()
+            .map_err(|_| format!("invalid number `{}`", arg));
+
+        match *args {
+            ["spawn", "asteroid", count] => {
+                let count: usize = count.parse()
+                    .map_err(|_| format!("invalid count `{}`", count))?;
+                for _ in 0..count {
+                    self.asteroid_factory.spawn(phi, &mut self.world, None, self.difficulty.pace());
+                }
+                Ok(format!("spawned {} asteroids", count))
+            },
+
+            ["spawn", "enemy", name] | ["spawn", "enemy", name, _] => {
+                let enemy_type = EnemyType::from_name(name)
+                    .ok_or_else(|| format!("unknown enemy `{}`", name))?;
+                let count: usize = match args.get(3) {
+                    Some(count) => count.parse()
+                        .map_err(|_| format!("invalid count `{}`", count))?,
+                    None => 1,
+                };
+                for _ in 0..count {
+                    self.enemy_factory.spawn(phi, &mut self.world, enemy_type, enemy_type.maneuver(), None, self.difficulty.pace());
+                }
+                Ok(format!("spawned {} {}s", count, name))
+            },
+
+            ["spawn", "boss"] => {
+                self.boss_factory.spawn(phi, &mut self.world, self.difficulty.pace());
+                Ok("spawned a boss".to_string())
+            },
+
+            ["god"] => {
+                self.god = !self.god;
+                Ok(format!("god mode {}", if self.god { "on" } else { "off" }))
+            },
+
+            ["friendlyfire"] => {
+                let matrix = &mut self.collision_matrix;
+                matrix.friendly_fire = !matrix.friendly_fire;
+                Ok(format!("friendly fire {}", if matrix.friendly_fire { "on" } else { "off" }))
+            },
+
+            ["cannon", "rect"] =>
+                self.set_cannon(CannonType::RectBullet),
+
+            ["cannon", "sine", amplitude, angular_vel] =>
+                self.set_cannon(CannonType::SineBullet {
+                    amplitude: number(amplitude)?,
+                    angular_vel: number(angular_vel)?,
+                }),
+
+            ["cannon", "divergent", a, b] =>
+                self.set_cannon(CannonType::DevergentBullet {
+                    a: number(a)?,
+                    b: number(b)?,
+                }),
+
+            _ => Err(format!("unknown command `{}`", args.join(" "))),
+        }
+    }
+
     fn render(&self, phi: &mut Phi) {
         // Clear the scene
         phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
         phi.renderer.clear();
 
+        let mut list = DrawList::new();
+
         // Render the Backgrounds
-        self.bg_back.render(&mut phi.renderer);
-        self.bg_middle.render(&mut phi.renderer);
+        self.backgrounds.render(&mut list);
 
         // Render the entities
 
-        self.player.render(phi);
+        list.custom(Layer::Scene, Z_EXHAUST, move |renderer| self.exhaust.render(renderer));
+        self.player.render(&mut list);
+
+        for (entity, kind) in self.world.kinds.iter() {
+            let transform = match self.world.transforms.get(entity) {
+                Some(transform) => transform,
+                None => continue,
+            };
+
+            match (kind, self.world.animators.get(entity)) {
+                (Kind::Bullet(bullet), _) =>
+                    render_bullet(&mut list, transform.rect, bullet.owner),
+
+                (Kind::Asteroid, Some(animator)) =>
+                    list.renderable_ex(Layer::Scene, Z_ASTEROIDS, animator, transform.rect, RenderOptions {
+                        angle: transform.angle,
+                        ..Default::default()
+                    }),
+
+                (Kind::Enemy(enemy), Some(animator)) =>
+                    list.renderable_ex(Layer::Scene, Z_ENEMIES, animator, transform.rect, RenderOptions {
+                        flip_horizontal: true,
+                        tint: enemy.enemy_type.tint(),
+                        ..Default::default()
+                    }),
+
+                (Kind::Boss(boss), Some(animator)) =>
+                    list.renderable_ex(Layer::Scene, Z_ENEMIES, animator, transform.rect, RenderOptions {
+                        flip_horizontal: true,
+                        tint: boss.tint(),
+                        ..Default::default()
+                    }),
+
+                (Kind::BossPart(_), Some(animator)) =>
+                    list.renderable_ex(Layer::Scene, Z_BOSS_PARTS, animator, transform.rect, RenderOptions {
+                        flip_horizontal: true,
+                        tint: BossPart::TINT,
+                        ..Default::default()
+                    }),
+
+                (Kind::Wreck(_), Some(animator)) =>
+                    list.renderable_ex(Layer::Scene, Z_ENEMIES, animator, transform.rect, RenderOptions {
+                        angle: transform.angle,
+                        flip_horizontal: true,
+                        tint: Wreck::TINT,
+                        ..Default::default()
+                    }),
+
+                // Fade the explosion out as its animation comes to an end.
+                (Kind::Explosion, Some(animator)) => {
+                    let remaining = 1.0 - animator.clip().progress();
+                    list.renderable_ex(Layer::Scene, Z_EXPLOSIONS, animator, transform.rect, RenderOptions {
+                        alpha: (remaining.max(0.0) * 255.0) as u8,
+                        ..Default::default()
+                    });
+                },
+
+                _ => {},
+            }
+        }
+
+        list.custom(Layer::Scene, Z_PARTICLES, move |renderer| self.sparks.render(renderer));
+        list.custom(Layer::Scene, Z_PARTICLES, move |renderer| self.debris.render(renderer));
+
+        // Render the collision boxes and describe the game in the debug overlay.
+        if phi.debug.visible {
+            let boxes = Color::RGB(200, 200, 50);
+            for (entity, collider) in self.world.colliders.iter() {
+                if let Some(transform) = self.world.transforms.get(entity) {
+                    let rect = transform.rect;
+                    list.outline(Layer::Debug, 0, rect, boxes);
+                    list.shape(Layer::Debug, 1, &collider.shape.translate(Vec2::new(rect.x, rect.y)), boxes);
+                }
+            }
 
-        for bullet in &self.bullets {
-            bullet.render(phi);
+            let (mut bullets, mut asteroids, mut enemies, mut bosses, mut explosions) = (0, 0, 0, 0, 0);
+            for (_, kind) in self.world.kinds.iter() {
+                match kind {
+                    Kind::Player => {},
+                    Kind::Bullet(_) => bullets += 1,
+                    Kind::Asteroid => asteroids += 1,
+                    Kind::Enemy(_) => enemies += 1,
+                    Kind::Boss(_) | Kind::BossPart(_) | Kind::Wreck(_) => bosses += 1,
+                    Kind::Explosion => explosions += 1,
+                }
+            }
+            phi.debug.line(format!("ENTITIES {}  BULLETS {}  ASTEROIDS {}",
+                self.world.len(), bullets, asteroids));
+            phi.debug.line(format!("ENEMIES {}  BOSSES {}  EXPLOSIONS {}", enemies, bosses, explosions));
+            phi.debug.line(format!("CANNON {}", self.player.cannon.describe()));
+            phi.debug.line(format!("DIFFICULTY {}", self.difficulty.describe()));
         }
 
-        for asteroid in &self.asteroids {
-            asteroid.render(phi);
+        // Render the score at the top of the screen.
+        let score = format!("SCORE {}", self.score);
+        let (score_w, _) = self.hud_font.measure(&score, HUD_SCALE);
+        let score_x = ((phi.output_size().0 - score_w) / 2.0).floor();
+        list.custom(Layer::Hud, 0, move |renderer| {
+            self.hud_font.render(renderer, &score, score_x, 10.0, HUD_SCALE);
+        });
+
+        // Render the health bars of the bosses at the bottom of the screen, gray
+        // while they are shielded.
+        let (screen_w, screen_h) = phi.output_size();
+        let bars = self.world.kinds.iter()
+            .filter_map(|(entity, kind)| match (kind, self.world.healths.get(entity)) {
+                (Kind::Boss(boss), Some(health)) => Some((boss, health)),
+                _ => None,
+            });
+        for (i, (boss, health)) in bars.enumerate() {
+            let frame = Rectangle {
+                x: screen_w * (1.0 - BOSS_BAR_W) / 2.0,
+                y: screen_h - (BOSS_BAR_H + 10.0) * (i + 2) as f64,
+                w: screen_w * BOSS_BAR_W,
+                h: BOSS_BAR_H,
+            };
+            let color = match boss.phase {
+                Phase::Shielded => Color::RGB(150, 150, 170),
+                Phase::Exposed => Color::RGB(250, 160, 40),
+                Phase::Enraged => Color::RGB(250, 50, 60),
+            };
+            let filled = health.points as f64 / boss.max_health as f64;
+            list.fill(Layer::Hud, 0, Rectangle { w: frame.w * filled, ..frame }, color);
+            list.outline(Layer::Hud, 1, frame, Color::RGB(255, 255, 255));
         }
 
-        for explosion in &self.explosions {
-            explosion.render(phi);
+        // Render the banner in the middle of the screen.
+        let banner_style = TextStyle {
+            align: Align::Center,
+            outline: Some((3, Color::RGB(70, 15, 70))),
+            ..TextStyle::new(FONT_PATH, 48, Color::RGB(255, 255, 255))
+        };
+
+        if let Some((ref text, _)) = self.banner {
+            if let Some(banner) = phi.text_sprite(text, &banner_style) {
+                let (w, h) = banner.size();
+                let dest = Rectangle::with_size(w, h).center_at(Rectangle::with_size(screen_w, screen_h).center());
+                list.custom(Layer::Hud, 0, move |renderer| renderer.copy_sprite(&banner, dest));
+            }
+        }
+
+        // Render the time elapsed since the start of the game, in the top-right
+        // corner of the screen.
+        let timer_style = TextStyle {
+            align: Align::Right,
+            outline: Some((2, Color::RGB(70, 15, 70))),
+            ..TextStyle::new(FONT_PATH, 24, Color::RGB(255, 255, 255))
+        };
+
+        if let Some(timer) = phi.text_sprite(&format!("{:.1}", self.play_time), &timer_style) {
+            let (w, h) = timer.size();
+            let dest = Rectangle {
+                w,
+                h,
+                x: phi.output_size().0 - w - 10.0,
+                y: 10.0,
+            };
+            list.custom(Layer::Hud, 0, move |renderer| renderer.copy_sprite(&timer, dest));
         }
 
-        // Render the foreground
-        self.bg_front.render(&mut phi.renderer);
+        phi.draw(list);
     }
 }
@@ -0,0 +1,132 @@
+use crate::phi::{Phi, View};
+use crate::phi::capture::save_pixels;
+use crate::views::difficulty::{Difficulty, Preset};
+use crate::views::game::GameView;
+use crate::views::main_menu::MainMenuView;
+use crate::views::shared::ParallaxStack;
+use sdl2::image::LoadSurface;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::surface::Surface;
+use std::fs;
+use std::path::Path;
+
+/// Where the reference images are stored.
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+
+/// The directory from which the game runs, and loads its assets, e.g.
+/// `assets/starBG.png`. Tests otherwise run from the root of the crate.
+const GAME_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+
+/// The seed of every run, so that asteroids and particles always end up in
+/// the same place.
+const GOLDEN_SEED: u64 = 42;
+
+/// How much a channel may differ from the reference before the pixel counts
+/// as different, to allow for rounding differences between platforms.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// The fraction of the pixels which may differ from the reference.
+const MAX_DIFFERENCE: f64 = 0.001;
+
+/// A view rendered in a known state.
+struct Case {
+    name: &'static str,
+
+    /// The number of frames the view runs before being rendered.
+    frames: u32,
+    init: fn(&mut Phi) -> Box<dyn View>,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "main_menu",
+        frames: 1,
+        init: |phi| Box::new(MainMenuView::new(phi)),
+    },
+    Case {
+        name: "game",
+        frames: 300,
+        init: |phi| {
+            let backgrounds = ParallaxStack::stars(phi);
+            Box::new(GameView::new(phi, backgrounds, Difficulty::new(Preset::Normal, false)))
+        },
+    },
+];
+
+/// Compares an image of the given size, made of tightly packed RGBA `pixels`,
+/// to the PNG image at `path`. Returns the fraction of the pixels which differ
+/// by more than `tolerance` on any channel.
+fn compare_png((w, h): (u32, u32), pixels: &[u8], path: &Path, tolerance: u8) -> Result<f64, String> {
+    let reference = Surface::from_file(path)?.convert_format(PixelFormatEnum::RGBA32)?;
+    if reference.size() != (w, h) {
+        return Err(format!("{}: expected a {}x{} image", path.display(), w, h));
+    }
+
+    let pitch = reference.pitch() as usize;
+    let row_len = w as usize * 4;
+    let differing = reference.with_lock(|expected| {
+        (0..h as usize)
+            .flat_map(|y| {
+                let expected = &expected[y * pitch..y * pitch + row_len];
+                let actual = &pixels[y * row_len..(y + 1) * row_len];
+                expected.chunks(4).zip(actual.chunks(4))
+            })
+            .filter(|(expected, actual)| expected.iter().zip(actual.iter())
+                .any(|(&e, &a)| (e as i16 - a as i16).unsigned_abs() > tolerance as u16))
+            .count()
+    });
+
+    Ok(differing as f64 / (w as f64 * h as f64))
+}
+
+/// Set, e.g. to 1, to record the reference images from the current renderings
+/// instead of comparing them, after a deliberate change to the visuals.
+const UPDATE_VAR: &str = "GOLDEN_UPDATE";
+
+/// Renders `case` and compares it to its reference image, or records it if
+/// `update` is set. When a rendering does not match, it is saved next to the
+/// reference for inspection.
+fn check(case: &Case, update: bool) -> Result<(), String> {
+    let reference = Path::new(GOLDEN_DIR).join(format!("{}.png", case.name));
+    let (size, mut pixels) = crate::phi::render_headless(GOLDEN_SEED, case.frames, case.init)?;
+
+    if update {
+        fs::create_dir_all(GOLDEN_DIR).map_err(|e| format!("{}: {}", GOLDEN_DIR, e))?;
+        return save_pixels(size, &mut pixels, &reference);
+    }
+
+    if !reference.exists() {
+        return Err(format!("{} is missing, record it with {}=1", reference.display(), UPDATE_VAR));
+    }
+
+    let difference = compare_png(size, &pixels, &reference, CHANNEL_TOLERANCE)?;
+    if difference <= MAX_DIFFERENCE {
+        return Ok(());
+    }
+
+    let actual = Path::new(GOLDEN_DIR).join(format!("{}.actual.png", case.name));
+    save_pixels(size, &mut pixels, &actual)?;
+    Err(format!("{:.2}% of the pixels differ, see {}", difference * 100.0, actual.display()))
+}
+
+/// Needs the SDL libraries, like the game, though neither a display nor a
+/// sound card.
+///
+/// The cases are checked one after the other in a single test, because SDL
+/// may only be initialized from one thread.
+#[test]
+fn views_match_their_reference_images() {
+    ::std::env::set_current_dir(GAME_DIR).unwrap();
+    for (var, driver) in [("SDL_VIDEODRIVER", "dummy"), ("SDL_AUDIODRIVER", "dummy")] {
+        if ::std::env::var_os(var).is_none() {
+            ::std::env::set_var(var, driver);
+        }
+    }
+
+    let update = ::std::env::var_os(UPDATE_VAR).is_some();
+    let failures: Vec<String> = CASES.iter()
+        .filter_map(|case| check(case, update).err().map(|e| format!("{}: {}", case.name, e)))
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -1,14 +1,29 @@
+use crate::phi::draw::{DrawList, Layer};
 use crate::phi::gfx::Sprite;
+use crate::phi::text::{Align, TextStyle};
 use crate::phi::{data::Rectangle, gfx::CopySprite, Phi, View, ViewAction};
-use crate::views::shared::Background;
+use crate::views::difficulty::{Difficulty, Preset};
+use crate::views::shared::ParallaxStack;
 use sdl2::pixels::Color;
 
+const FONT_PATH: &str = "assets/belligerent.ttf";
+
+/// Reminds the player of the controls, below the menu.
+const CONTROLS_HINT: &str =
+    "Move with the arrow keys, shoot with space and change cannons with 1, 2 and 3";
+
+/// What a menu entry does once chosen.
+type ActionFn = Box<dyn Fn(&mut Phi, ParallaxStack) -> ViewAction>;
 
 struct Action {
-    /// The function which should be executed if the action is chosen
+    /// The text shown in the menu.
+    label: String,
+
+    /// The function which should be executed if the action is chosen, given
+    /// the menu's backgrounds so that the next view may keep them.
     ///? Westore it in a Box because, as we saw previously, 'Fn' is a trait
     //? and we may only interact with unsized data through a pointer
-    func: Box< dyn Fn(&mut Phi) -> ViewAction>,
+    func: ActionFn,
     
     /// The sprite which is rendered when the player does not focus on this
     /// action's label.
@@ -20,11 +35,23 @@ struct Action {
 }
 
 impl Action {
-    fn new(phi: &mut Phi, label: &'static str, func: Box<dyn Fn(&mut Phi) -> ViewAction>) -> Action {
+    fn new(phi: &mut Phi, label: &str, func: ActionFn) -> Action {
         Action {
+            label: label.to_string(),
             func: func,
-            idle_sprite: phi.ttf_str_sprite(label, "assets/belligerent.ttf", 32, Color::RGB(220, 220, 220)).unwrap(),
-            hover_sprite: phi.ttf_str_sprite(label, "assets/belligerent.ttf", 38, Color::RGB(255, 255, 255)).unwrap(),
+            idle_sprite: phi.text_sprite(label, &Action::idle_style()).unwrap(),
+            hover_sprite: phi.text_sprite(label, &Action::hover_style()).unwrap(),
+        }
+    }
+
+    fn idle_style() -> TextStyle {
+        TextStyle::new(FONT_PATH, 32, Color::RGB(220, 220, 220))
+    }
+
+    fn hover_style() -> TextStyle {
+        TextStyle {
+            shadow: Some(((2, 2), Color::RGB(70, 15, 70))),
+            ..TextStyle::new(FONT_PATH, 38, Color::RGB(255, 255, 255))
         }
     }
 }
@@ -33,41 +60,47 @@ pub struct MainMenuView {
     actions: Vec<Action>,
     selected: i8,
 
-    bg_back: Background,
-    bg_middle: Background,
-    bg_front: Background,
+    backgrounds: ParallaxStack,
 }
 
 impl MainMenuView {
     pub fn new(phi: &mut Phi) -> MainMenuView {
+        let backgrounds = ParallaxStack::stars(phi);
+        MainMenuView::with_backgrounds(phi, backgrounds, Difficulty::new(Preset::Normal, false))
+    }
+
+    /// Shows the menu in front of `backgrounds`, which keep scrolling from
+    /// where they were, with `difficulty` chosen for the next game.
+    pub fn with_backgrounds(phi: &mut Phi, backgrounds: ParallaxStack, difficulty: Difficulty) -> MainMenuView {
+        // Changing the difficulty shows the menu again, with the new choice
+        // and the same entry selected.
+        let choose = |difficulty: Difficulty, selected: i8| -> ActionFn {
+            Box::new(move |phi, backgrounds| {
+                let mut menu = MainMenuView::with_backgrounds(phi, backgrounds, difficulty);
+                menu.selected = selected;
+                ViewAction::Render(Box::new(menu))
+            })
+        };
+
+        let preset_label = format!("Difficulty: {}", difficulty.preset.name());
+        let adaptive_label = format!("Adaptive: {}", if difficulty.adaptive { "On" } else { "Off" });
+
         MainMenuView {
             actions: vec![
-                Action::new(phi, "New Game", Box::new(|phi| {
-                    ViewAction::Render(Box::new(crate::views::game::GameView::new(phi)))
+                Action::new(phi, "New Game", Box::new(move |phi, backgrounds| {
+                    ViewAction::Render(Box::new(crate::views::game::GameView::new(phi, backgrounds, difficulty)))
                 })),
-                Action::new(phi, "Quit", Box::new(|_| {
+                Action::new(phi, &preset_label,
+                    choose(Difficulty::new(difficulty.preset.next(), difficulty.adaptive), 1)),
+                Action::new(phi, &adaptive_label,
+                    choose(Difficulty::new(difficulty.preset, !difficulty.adaptive), 2)),
+                Action::new(phi, "Quit", Box::new(|_, _| {
                     ViewAction::Quit
                 })),
             ],
             selected: 0,
 
-            bg_back: Background {
-                pos: 0.0,
-                vel: 20.0,
-                sprite: Sprite::load(&mut phi.renderer, "assets/starBG.png").unwrap(),
-            },
-
-            bg_middle: Background {
-                pos: 0.0,
-                vel: 40.0,
-                sprite: Sprite::load(&mut phi.renderer, "assets/starMG.png").unwrap(),
-            },
-
-            bg_front: Background {
-                pos: 0.0,
-                vel: 80.0,
-                sprite: Sprite::load(&mut phi.renderer, "assets/starFG.png").unwrap(),
-            },
+            backgrounds,
         }
     }
 }
@@ -81,7 +114,8 @@ impl View for MainMenuView {
         // Execute the currently selected action if requested
         if phi.events.now.key_space == Some(true) || 
            phi.events.now.key_enter == Some(true) {
-               return (self.actions[self.selected as usize].func)(phi);
+               let backgrounds = self.backgrounds.clone();
+               return (self.actions[self.selected as usize].func)(phi, backgrounds);
         }
 
         // Change the selected action using the keyboard
@@ -100,9 +134,7 @@ impl View for MainMenuView {
         }
 
         // Update the backgrounds
-        self.bg_back.update(elapsed);
-        self.bg_middle.update(elapsed);
-        self.bg_front.update(elapsed);
+        self.backgrounds.update(elapsed);
 
         ViewAction::Render(self)
     }
@@ -112,56 +144,74 @@ impl View for MainMenuView {
         phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
         phi.renderer.clear();
 
+        let mut list = DrawList::new();
+
         // Render the backgrounds
-        self.bg_back.render(&mut phi.renderer);
-        self.bg_middle.render(&mut phi.renderer);
-        self.bg_front.render(&mut phi.renderer);
+        self.backgrounds.render(&mut list);
         
         // Definitions for the menu's layout
         let (win_w, win_h) = phi.output_size();
         let label_h = 50.0;
         let border_width = 3.0;
-        let box_w = 360.0;
+        let margin_w = 20.0;
+
+        // The box is wide enough for the longest label to be highlighted.
+        let box_w = self.actions.iter()
+            .filter_map(|action| phi.text.measure(&action.label, &Action::hover_style()))
+            .fold(360.0, |box_w: f64, (w, _)| box_w.max(w + margin_w * 2.0));
         let box_h = self.actions.len() as f64 * label_h;
         let margin_h = 10.0;
 
          // Render the border of the colored box which holds the labels
-         phi.renderer.set_draw_color(Color::RGB(70, 15, 70));
-         phi.renderer.fill_rect(Rectangle {
+         list.fill(Layer::Hud, 0, Rectangle {
              w: box_w + border_width * 2.0,
              h: box_h + border_width * 2.0 + margin_h * 2.0,
              x: (win_w - box_w) / 2.0 - border_width,
              y: (win_h - box_h) / 2.0 - margin_h - border_width,
-         }.to_sdl()).unwrap();
+         }, Color::RGB(70, 15, 70));
 
          // Render the colored box which holds the labels
-        phi.renderer.set_draw_color(Color::RGB(140, 30, 140));
-        phi.renderer.fill_rect(Rectangle {
+        list.fill(Layer::Hud, 0, Rectangle {
             w: box_w,
             h: box_h + margin_h * 2.0,
             x: (win_w - box_w) / 2.0,
             y: (win_h - box_h) / 2.0 - margin_h,
-        }.to_sdl()).unwrap();
+        }, Color::RGB(140, 30, 140));
 
         // Render the labels in the menu
         for (i, action) in self.actions.iter().enumerate() {
-            if self.selected as usize == i {
-                let (w, h) = action.hover_sprite.size();
-                phi.renderer.copy_sprite(&action.hover_sprite, Rectangle {
-                    w: w,
-                    h: h,
-                    x: (win_w - w) / 2.0,
-                    y: (win_h - box_h + label_h - h) / 2.0 + label_h * i as f64,
-                });
-            } else {
-                let (w, h) = action.idle_sprite.size();
-                phi.renderer.copy_sprite(&action.idle_sprite, Rectangle {
-                    w: w,
-                    h: h,
-                    x: (win_w - w) / 2.0,
-                    y: (win_h - box_h + label_h - h) / 2.0 + label_h * i as f64,
-                });
-            }
+            let sprite =
+                if self.selected as usize == i { &action.hover_sprite }
+                else { &action.idle_sprite };
+
+            let (w, h) = sprite.size();
+            list.renderable(Layer::Hud, 1, sprite, Rectangle {
+                w,
+                h,
+                x: (win_w - w) / 2.0,
+                y: (win_h - box_h + label_h - h) / 2.0 + label_h * i as f64,
+            });
         }
+
+        // Render the controls below the box.
+        let hint_style = TextStyle {
+            align: Align::Center,
+            wrap_width: Some(box_w as u32),
+            shadow: Some(((1, 1), Color::RGB(0, 0, 0))),
+            ..TextStyle::new(FONT_PATH, 18, Color::RGB(180, 180, 180))
+        };
+
+        if let Some(hint) = phi.text_sprite(CONTROLS_HINT, &hint_style) {
+            let (w, h) = hint.size();
+            let dest = Rectangle {
+                w,
+                h,
+                x: (win_w - w) / 2.0,
+                y: (win_h + box_h) / 2.0 + margin_h + border_width + label_h / 2.0,
+            };
+            list.custom(Layer::Hud, 1, move |renderer| renderer.copy_sprite(&hint, dest));
+        }
+
+        phi.draw(list);
     }
-}
\ No newline at end of file
+}
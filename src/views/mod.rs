@@ -1,4 +1,11 @@
+pub mod bosses;
+pub mod difficulty;
+pub mod enemies;
+pub mod entities;
 pub mod game;
+#[cfg(test)]
+mod golden;
 pub mod main_menu;
 pub mod shared;
+pub mod stages;
 pub mod bullets;
\ No newline at end of file
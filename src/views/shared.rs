@@ -1,13 +1,46 @@
+use crate::phi::Phi;
 use crate::phi::data::Rectangle;
+use crate::phi::draw::{DrawList, Layer};
 use crate::phi::gfx::{Sprite, CopySprite};
 use sdl2::render::WindowCanvas;
 
+// The order in which things are rendered within a layer, from the back to the
+// front.
+pub const Z_EXHAUST: i32 = 0;
+pub const Z_PLAYER: i32 = 1;
+pub const Z_ENEMIES: i32 = 2;
+pub const Z_BOSS_PARTS: i32 = 3;
+pub const Z_BULLETS: i32 = 4;
+pub const Z_ASTEROIDS: i32 = 5;
+pub const Z_EXPLOSIONS: i32 = 6;
+pub const Z_PARTICLES: i32 = 7;
+
+/// How the image of a background is scaled before being tiled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScaleMode {
+    /// Scaled so that a tile is as high as the window.
+    FitHeight,
+
+    /// Scaled so that a tile is as wide as the window.
+    FitWidth,
+}
+
+/// Moves the position `pos` of a background along one axis, at `vel` pixels per
+/// second for `elapsed` seconds, and wraps it around, whichever way it goes, so
+/// that it stays within the `size` pixels of the image.
+fn scroll(pos: f64, vel: f64, elapsed: f64, size: f64) -> f64 {
+    (pos + vel * elapsed).rem_euclid(size)
+}
+
 #[derive(Clone)]
 pub struct Background {
-    pub pos: f64,
-    // The amount of pixels moved to the left every second
-    pub vel: f64,
+    /// The position of the layer, in pixels of the image, along both axes.
+    pub pos: (f64, f64),
+    // The amount of pixels moved to the left and to the top every second.
+    // Negative velocities move the layer to the right and to the bottom.
+    pub vel: (f64, f64),
     pub sprite: Sprite,
+    pub scale: ScaleMode,
 }
 
 impl Background {
@@ -17,33 +50,130 @@ impl Background {
         // We define a logical position as depending solely on the time and the
         // dimensions of the image, not on the screen's size.
         let size = self.sprite.size();
-        self.pos += self.vel * elapsed;
-        if self.pos > size.0 {
-            self.pos -= size.0;
-        }
+        self.pos.0 = scroll(self.pos.0, self.vel.0, elapsed, size.0);
+        self.pos.1 = scroll(self.pos.1, self.vel.1, elapsed, size.1);
     }
 
-    /// Render the background at ist current position, and as many times as
+    /// Render the background at its current position, and as many times as
     /// required to fill the screen.
     pub fn render(&self, renderer: &mut WindowCanvas) {
-        // We determine the scale ratio of the window to the sprte.
+        // We determine the scale ratio of the window to the sprite.
         let size = self.sprite.size();
         let (win_w, win_h) = renderer.output_size().unwrap();
-        let scale = win_h as f64 / size.1;
+        let (win_w, win_h) = (win_w as f64, win_h as f64);
+        let scale = match self.scale {
+            ScaleMode::FitHeight => win_h / size.1,
+            ScaleMode::FitWidth => win_w / size.0,
+        };
+        let (tile_w, tile_h) = (size.0 * scale, size.1 * scale);
 
         // We render as many copies of the background as necessary to fill
-        // the screen.
-        let mut physical_left = -self.pos * scale;
-
-        while physical_left < win_w as f64 {
-            renderer.copy_sprite(&self.sprite, Rectangle {
-                x: physical_left,
-                y: 0.0,
-                w: size.0 * scale,
-                h: win_h as f64,
-            });
-
-            physical_left += size.0 * scale;
+        // the screen. Because the position is kept within the image, the first
+        // tile always starts on the left of, or at, the edge of the screen.
+        let mut physical_top = -self.pos.1 * scale;
+
+        while physical_top < win_h {
+            let mut physical_left = -self.pos.0 * scale;
+
+            while physical_left < win_w {
+                renderer.copy_sprite(&self.sprite, Rectangle {
+                    x: physical_left,
+                    y: physical_top,
+                    w: tile_w,
+                    h: tile_h,
+                });
+
+                physical_left += tile_w;
+            }
+
+            physical_top += tile_h;
         }
     }   
-}
\ No newline at end of file
+}
+
+/// The layers of background scrolling at different speeds to give an illusion
+/// of depth. Views pass their stack along to the next one, so that the
+/// backgrounds do not jump when the player navigates between them.
+#[derive(Clone)]
+pub struct ParallaxStack {
+    /// The layers rendered behind the scene, from the back to the front.
+    pub back: Vec<Background>,
+
+    /// The layers rendered in front of the scene, from the back to the front.
+    pub front: Vec<Background>,
+}
+
+impl ParallaxStack {
+    /// The starfield scrolling to the left, shared by the menu and the game.
+    pub fn stars(phi: &mut Phi) -> ParallaxStack {
+        let layer = |phi: &mut Phi, path: &str, vel: f64, scale: ScaleMode| Background {
+            pos: (0.0, 0.0),
+            vel: (vel, 0.0),
+            sprite: Sprite::load(&phi.renderer, path).unwrap(),
+            scale,
+        };
+
+        // The images are as large as the default window. When it is resized,
+        // the stars grow with it.
+        ParallaxStack {
+            back: vec![
+                layer(phi, "assets/starBG.png", 20.0, ScaleMode::FitHeight),
+                layer(phi, "assets/starMG.png", 40.0, ScaleMode::FitHeight),
+            ],
+            front: vec![
+                layer(phi, "assets/starFG.png", 80.0, ScaleMode::FitWidth),
+            ],
+        }
+    }
+
+    pub fn update(&mut self, elapsed: f64) {
+        for background in self.back.iter_mut().chain(self.front.iter_mut()) {
+            background.update(elapsed);
+        }
+    }
+
+    /// Submit every layer, in the `Background` and `Foreground` layers of the
+    /// list.
+    pub fn render<'a>(&'a self, list: &mut DrawList<'a>) {
+        for (z, background) in self.back.iter().enumerate() {
+            list.custom(Layer::Background, z as i32, move |renderer| background.render(renderer));
+        }
+
+        for (z, background) in self.front.iter().enumerate() {
+            list.custom(Layer::Foreground, z as i32, move |renderer| background.render(renderer));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backgrounds_scroll_within_their_image() {
+        assert_eq!(scroll(10.0, 20.0, 0.5, 100.0), 20.0);
+        assert_eq!(scroll(90.0, 20.0, 1.0, 100.0), 10.0);
+        assert_eq!(scroll(0.0, 0.0, 1.0, 100.0), 0.0);
+    }
+
+    #[test]
+    fn negative_velocities_wrap_around() {
+        assert_eq!(scroll(10.0, -20.0, 1.0, 100.0), 90.0);
+        assert_eq!(scroll(0.0, -25.0, 1.0, 100.0), 75.0);
+    }
+
+    #[test]
+    fn vertical_scrolling_wraps_around_the_height() {
+        // Both axes of a background scroll alike, each within its own size.
+        let (w, h) = (800.0, 600.0);
+        assert_eq!((scroll(0.0, 40.0, 1.0, w), scroll(590.0, 40.0, 1.0, h)), (40.0, 30.0));
+        assert_eq!((scroll(0.0, 0.0, 1.0, w), scroll(10.0, -40.0, 1.0, h)), (0.0, 570.0));
+    }
+
+    #[test]
+    fn scrolling_over_several_images_wraps_around_as_many_times() {
+        assert_eq!(scroll(10.0, 250.0, 1.0, 100.0), 60.0);
+        assert_eq!(scroll(10.0, -250.0, 1.0, 100.0), 60.0);
+        assert_eq!(scroll(0.0, 100.0, 3.0, 100.0), 0.0);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::views::enemies::{EnemyType, Maneuver};
+use serde::Deserialize;
+use std::fs;
+
+/// Something which a stage sends at the player.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Spawnable {
+    Asteroid,
+
+    /// An enemy ship, flying the maneuver of its type unless another is given.
+    Enemy {
+        ship: EnemyType,
+        #[serde(default)]
+        maneuver: Option<Maneuver>,
+    },
+
+    Boss,
+}
+
+/// A group of things appearing one after the other during a wave.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct SpawnDescr {
+    /// When the first of them appears, in seconds since the wave started.
+    at: f64,
+    pub what: Spawnable,
+
+    /// The height at which they appear, from 0, at the top of the screen, to
+    /// 1, at the bottom. If `None`, every one of them appears at random.
+    #[serde(default)]
+    pub height: Option<f64>,
+
+    /// How many of them appear, one every `interval` seconds.
+    #[serde(default = "SpawnDescr::one")]
+    count: u32,
+    #[serde(default)]
+    interval: f64,
+}
+
+impl SpawnDescr {
+    fn one() -> u32 {
+        1
+    }
+
+    /// How many of the group have appeared once the wave lasted `time`
+    /// seconds, however long its frames were.
+    fn due(&self, time: f64) -> u32 {
+        if time < self.at {
+            0
+        } else if self.interval <= 0.0 || time >= self.end() {
+            self.count
+        } else {
+            (((time - self.at) / self.interval) as u32 + 1).min(self.count)
+        }
+    }
+
+    /// When the last of the group appears, in seconds since the wave started.
+    fn end(&self) -> f64 {
+        self.at + self.interval * self.count.saturating_sub(1) as f64
+    }
+}
+
+/// A timeline of things appearing, which starts once the previous wave is
+/// over.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WaveDescr {
+    /// The time, in seconds, between the end of the previous wave and the
+    /// start of this one.
+    #[serde(default)]
+    delay: f64,
+
+    /// Whether the wave waits, before its delay, for everything which the
+    /// previous ones sent to be destroyed or gone, e.g. before a boss.
+    #[serde(default)]
+    after_clear: bool,
+
+    spawns: Vec<SpawnDescr>,
+}
+
+/// The content of a stage file, stored in RON.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StageDescr {
+    name: String,
+    waves: Vec<WaveDescr>,
+}
+
+impl StageDescr {
+    /// Returns an error message if a time is negative or a height is off the
+    /// screen, which the file format alone does not rule out.
+    fn validate(&self) -> Result<(), String> {
+        for (index, wave) in self.waves.iter().enumerate() {
+            let wave_error = |message: &str| Err(format!("wave {}: {}", index + 1, message));
+
+            if wave.delay < 0.0 {
+                return wave_error("the delay must not be negative");
+            }
+            for spawn in &wave.spawns {
+                if spawn.at < 0.0 || spawn.interval < 0.0 {
+                    return wave_error("spawn times must not be negative");
+                }
+                if let Some(height) = spawn.height {
+                    if !(0.0..=1.0).contains(&height) {
+                        return wave_error("heights must be between 0 and 1");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Plays the waves of a stage one after the other, telling the game what
+/// appears and when.
+pub struct StageRunner {
+    descr: StageDescr,
+
+    /// The wave being played, or waited for, which is past the last one once
+    /// the stage is over.
+    wave: usize,
+    started: bool,
+
+    /// The time, in seconds, since the current wave started, or, while it
+    /// waits, since the previous one ended.
+    time: f64,
+
+    /// How many of every group of the current wave have appeared.
+    spawned: Vec<u32>,
+}
+
+impl StageRunner {
+    /// Loads the stage described by the RON file at `path`. Returns an error
+    /// message if it could not be read, or is not a valid stage.
+    pub fn load(path: &str) -> Result<StageRunner, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        StageRunner::parse(&source)
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Reads the stage described by `source`, in RON.
+    fn parse(source: &str) -> Result<StageRunner, String> {
+        let descr: StageDescr = ron::from_str(source)
+            .map_err(|e| e.to_string())?;
+        descr.validate()?;
+
+        Ok(StageRunner {
+            descr,
+            wave: 0,
+            started: false,
+            time: 0.0,
+            spawned: vec![],
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.descr.name
+    }
+
+    /// Whether every wave has been sent. The stage is cleared once whatever
+    /// they sent is gone.
+    pub fn is_over(&self) -> bool {
+        self.wave >= self.descr.waves.len()
+    }
+
+    /// Plays the stage for `dt` seconds, given whether everything which it
+    /// sent so far is `cleared`. Returns what appears during that time, in
+    /// order.
+    pub fn update(&mut self, dt: f64, cleared: bool) -> Vec<SpawnDescr> {
+        let mut due = vec![];
+        self.time += dt;
+
+        while let Some(wave) = self.descr.waves.get(self.wave) {
+            if !self.started {
+                // Whatever appeared during this frame is still there.
+                if wave.after_clear && !(cleared && due.is_empty()) {
+                    self.time = 0.0;
+                    break;
+                }
+                if self.time < wave.delay {
+                    break;
+                }
+
+                self.time -= wave.delay;
+                self.started = true;
+                self.spawned = vec![0; wave.spawns.len()];
+            }
+
+            for (spawn, spawned) in wave.spawns.iter().zip(&mut self.spawned) {
+                while *spawned < spawn.due(self.time) {
+                    due.push(*spawn);
+                    *spawned += 1;
+                }
+            }
+
+            // The next wave starts as long after the end of this one as this
+            // frame went on.
+            let end = wave.spawns.iter().map(SpawnDescr::end).fold(0.0, f64::max);
+            if self.time < end {
+                break;
+            }
+            self.time -= end;
+            self.wave += 1;
+            self.started = false;
+        }
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays `stage` for `duration` seconds, in frames of `dt` seconds, and
+    /// returns how many things appeared.
+    fn play(stage: &str, dt: f64, duration: f64, cleared: bool) -> usize {
+        let mut runner = StageRunner::parse(stage).unwrap();
+        let mut time = 0.0;
+        let mut spawned = 0;
+
+        while time < duration {
+            let dt = dt.min(duration - time);
+            spawned += runner.update(dt, cleared).len();
+            time += dt;
+        }
+        spawned
+    }
+
+    fn spawn(at: f64, count: u32, interval: f64) -> SpawnDescr {
+        SpawnDescr { at, what: Spawnable::Asteroid, height: None, count, interval }
+    }
+
+    #[test]
+    fn due_counts_the_spawns_started_so_far() {
+        let group = spawn(1.0, 3, 0.5);
+        assert_eq!(group.due(0.9), 0);
+        assert_eq!(group.due(1.0), 1);
+        assert_eq!(group.due(1.4), 1);
+        assert_eq!(group.due(1.5), 2);
+        assert_eq!(group.due(2.0), 3);
+        assert_eq!(group.due(100.0), 3);
+        assert_eq!(group.end(), 2.0);
+    }
+
+    #[test]
+    fn zero_interval_spawns_the_whole_group_at_once() {
+        let group = spawn(1.0, 4, 0.0);
+        assert_eq!(group.due(0.9), 0);
+        assert_eq!(group.due(1.0), 4);
+        assert_eq!(group.end(), 1.0);
+
+        let stage = "(name: \"\", waves: [(spawns: [(at: 0.0, what: Asteroid, count: 4)])])";
+        assert_eq!(StageRunner::parse(stage).unwrap().update(0.0, true).len(), 4);
+    }
+
+    #[test]
+    fn spawns_do_not_depend_on_the_frame_rate() {
+        let stage = "(name: \"\", waves: [
+            (delay: 0.5, spawns: [(at: 0.0, what: Asteroid, count: 5, interval: 0.3)]),
+            (delay: 1.0, spawns: [
+                (at: 0.2, what: Asteroid, count: 3, interval: 0.7),
+                (at: 1.1, what: Boss),
+            ]),
+        ])";
+
+        for duration in [0.4, 1.0, 2.35, 3.5, 10.0] {
+            let smooth = play(stage, 1.0 / 60.0, duration, true);
+            assert_eq!(play(stage, 1.0 / 7.0, duration, true), smooth, "after {}s", duration);
+            assert_eq!(play(stage, duration, duration, true), smooth, "after {}s", duration);
+        }
+        assert_eq!(play(stage, 1.0 / 60.0, 10.0, true), 9);
+    }
+
+    #[test]
+    fn after_clear_waits_for_the_screen_to_be_cleared() {
+        let stage = "(name: \"\", waves: [
+            (spawns: [(at: 0.0, what: Asteroid)]),
+            (delay: 1.0, after_clear: true, spawns: [(at: 0.0, what: Boss)]),
+        ])";
+        let mut runner = StageRunner::parse(stage).unwrap();
+
+        // What the first wave sent is still there during the same frame.
+        assert_eq!(runner.update(5.0, true).len(), 1);
+        assert_eq!(runner.update(5.0, false).len(), 0);
+        assert!(!runner.is_over());
+
+        // The delay only starts once the screen is clear.
+        assert_eq!(runner.update(0.5, true).len(), 0);
+        assert_eq!(runner.update(0.6, true).len(), 1);
+        assert!(runner.is_over());
+    }
+
+    #[test]
+    fn empty_waves_do_not_stall_the_stage() {
+        let stage = "(name: \"\", waves: [
+            (spawns: []),
+            (delay: 1.0, spawns: []),
+            (spawns: [(at: 0.0, what: Asteroid)]),
+            (spawns: []),
+        ])";
+        let mut runner = StageRunner::parse(stage).unwrap();
+
+        assert_eq!(runner.update(0.5, true).len(), 0);
+        assert_eq!(runner.update(0.5, true).len(), 1);
+        assert!(runner.is_over());
+    }
+
+    #[test]
+    fn the_stages_of_the_game_are_valid() {
+        for source in [include_str!("../assets/stages/stage1.ron"), include_str!("../assets/stages/stage2.ron")] {
+            let runner = StageRunner::parse(source).unwrap();
+            assert!(!runner.is_over());
+        }
+    }
+
+    #[test]
+    fn invalid_stages_are_rejected() {
+        assert!(StageRunner::parse("(name: \"\", waves: [(delay: -1.0, spawns: [])])").is_err());
+        assert!(StageRunner::parse("(name: \"\", waves: [(spawns: [(at: -1.0, what: Boss)])])").is_err());
+        assert!(StageRunner::parse("(name: \"\", waves: [(spawns: [(at: 0.0, what: Boss, height: Some(1.5))])])").is_err());
+        assert!(StageRunner::parse("(name: \"\", waves: [(spawns: [(at: 0.0, what: Dragon)])])").is_err());
+    }
+}